@@ -3,49 +3,53 @@ use serde::{Deserialize, Serialize};
 use std::fs::File;
 use std::io::BufReader;
 
+use crate::{error::Error, network};
+
 #[derive(Serialize, Deserialize, Debug)]
 struct Alias(std::collections::HashMap<String, String>);
-#[allow(dead_code)]
-pub fn alias_token(token_add: &String) -> Result<Address, Box<dyn std::error::Error>> {
-    let file = File::open("alias/alias_token.json")?;
-    let reader = BufReader::new(file);
-
-    let tokens: Alias = serde_json::from_reader(reader)?;
-    let mut token = None;
-
-    for (name, address) in &tokens.0 {
-        if token_add == name {
-            token = Some(address.parse::<Address>()?);
-            break;
-        }
-    }
-
-    match token {
-        Some(t) => Ok(t),
-        None => token_add
-            .parse::<Address>()
-            .map_err(|e| Box::new(e) as Box<dyn std::error::Error>),
+// Token alias of the network profile (tokens in config/networks.json) or an address
+#[allow(clippy::ptr_arg)]
+pub fn alias_token(token_add: &String) -> Result<Address, Error> {
+    match network::token(token_add) {
+        Some(token) => Ok(token),
+        None => parse_address(token_add),
     }
 }
 #[allow(dead_code)]
-pub fn alias_address(router_add: &String) -> Result<Address, Box<dyn std::error::Error>> {
-    let file = File::open("alias/alias_address.json")?;
-    let reader = BufReader::new(file);
-
-    let addresses: Alias = serde_json::from_reader(reader)?;
+pub fn alias_address(router_add: &String) -> Result<Address, Error> {
+    let addresses = read_alias("alias/alias_address.json")?;
     let mut router = None;
 
     for (name, address) in &addresses.0 {
         if router_add == name {
-            router = Some(address.parse::<Address>()?);
+            router = Some(parse_address(address)?);
             break;
         }
     }
 
     match router {
         Some(t) => Ok(t),
-        None => router_add
-            .parse::<Address>()
-            .map_err(|e| Box::new(e) as Box<dyn std::error::Error>),
+        None => parse_address(router_add),
     }
 }
+// Every token alias of the network profile, sorted by name
+pub fn alias_tokens() -> Vec<(String, Address)> {
+    network::get()
+        .tokens
+        .iter()
+        .map(|(name, address)| (name.clone(), *address))
+        .collect()
+}
+
+fn read_alias(path: &str) -> Result<Alias, Error> {
+    let file = File::open(path).map_err(|e| Error::Config(format!("{}: {}", path, e)))?;
+    let reader = BufReader::new(file);
+    serde_json::from_reader(reader).map_err(|e| Error::Config(format!("{}: {}", path, e)))
+}
+
+// Neither an alias nor a valid address
+fn parse_address(address: &str) -> Result<Address, Error> {
+    address
+        .parse::<Address>()
+        .map_err(|_| Error::Alias(address.to_string()))
+}
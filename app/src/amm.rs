@@ -0,0 +1,222 @@
+use ethers::core::types::{U256, U512};
+use eyre::Result;
+
+use crate::error::Error;
+
+// UniswapV2 constant product math on fetched reserves, same integer results as UniswapV2Library.
+// Reserves of a path are given per hop as (reserve of the input token, reserve of the output token).
+
+const BPS: u64 = 10000;
+
+// UniswapV2Library.getAmountOut with the swap fee of the dex (30 bps on Uniswap)
+pub fn get_amount_out(
+    amount_in: U256,
+    reserve_in: U256,
+    reserve_out: U256,
+    fee_bps: u32,
+) -> Result<U256> {
+    if amount_in.is_zero() {
+        return Err(Error::Aborted("Insufficient input amount".to_string()).into());
+    }
+    if reserve_in.is_zero() || reserve_out.is_zero() {
+        return Err(Error::Aborted("Insufficient liquidity".to_string()).into());
+    }
+    let amount_in_with_fee = amount_in * U256::from(BPS - fee_bps as u64);
+    Ok(amount_in_with_fee * reserve_out / (reserve_in * U256::from(BPS) + amount_in_with_fee))
+}
+
+// UniswapV2Library.getAmountIn, rounded up like the library
+pub fn get_amount_in(
+    amount_out: U256,
+    reserve_in: U256,
+    reserve_out: U256,
+    fee_bps: u32,
+) -> Result<U256> {
+    if amount_out.is_zero() {
+        return Err(Error::Aborted("Insufficient output amount".to_string()).into());
+    }
+    if reserve_in.is_zero() || amount_out >= reserve_out {
+        return Err(Error::Aborted("Insufficient liquidity".to_string()).into());
+    }
+    let numerator = reserve_in * amount_out * U256::from(BPS);
+    let denominator = (reserve_out - amount_out) * U256::from(BPS - fee_bps as u64);
+    Ok(numerator / denominator + 1)
+}
+
+// getAmountsOut chained along the hops of a path
+pub fn get_amounts_out(
+    amount_in: U256,
+    reserves: &[(U256, U256)],
+    fee_bps: u32,
+) -> Result<Vec<U256>> {
+    let mut amounts = vec![amount_in];
+    for (reserve_in, reserve_out) in reserves {
+        let amount = get_amount_out(
+            amounts[amounts.len() - 1],
+            *reserve_in,
+            *reserve_out,
+            fee_bps,
+        )?;
+        amounts.push(amount);
+    }
+    Ok(amounts)
+}
+
+// getAmountsIn chained backwards along the hops of a path
+pub fn get_amounts_in(
+    amount_out: U256,
+    reserves: &[(U256, U256)],
+    fee_bps: u32,
+) -> Result<Vec<U256>> {
+    let mut amounts = vec![amount_out];
+    for (reserve_in, reserve_out) in reserves.iter().rev() {
+        let amount = get_amount_in(amounts[0], *reserve_in, *reserve_out, fee_bps)?;
+        amounts.insert(0, amount);
+    }
+    Ok(amounts)
+}
+
+// Output of amount_in at the mid price of the path, without fee nor price impact, in U512 so a
+// large unit (10^decimals) cannot overflow
+pub fn mid_amount_out(amount_in: U256, reserves: &[(U256, U256)]) -> U256 {
+    reserves
+        .iter()
+        .fold(amount_in, |amount, (reserve_in, reserve_out)| {
+            if reserve_in.is_zero() {
+                U256::zero()
+            } else {
+                U256::try_from(amount.full_mul(*reserve_out) / U512::from(*reserve_in))
+                    .unwrap_or(U256::MAX)
+            }
+        })
+}
+
+// Shortfall of amount_out against the mid price output in bps, the swap fee included
+// (same definition as the Uniswap interface)
+pub fn shortfall_bps(mid: U256, amount_out: U256) -> u64 {
+    if mid.is_zero() || amount_out >= mid {
+        return 0;
+    }
+    ((mid - amount_out).full_mul(U256::from(BPS)) / U512::from(mid)).as_u64()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn ether(amount: u64) -> U256 {
+        U256::exp10(18) * amount
+    }
+
+    // Swap cases of the UniswapV2Pair tests: (amount in, reserve in, reserve out) in ether
+    const CASES: [(u64, u64, u64); 7] = [
+        (1, 5, 10),
+        (1, 10, 5),
+        (2, 5, 10),
+        (2, 10, 5),
+        (1, 10, 10),
+        (1, 100, 100),
+        (1, 1000, 1000),
+    ];
+
+    #[test]
+    fn amount_out_matches_uniswap_v2() {
+        let expected = [
+            "1662497915624478906",
+            "453305446940074565",
+            "2851015155847869602",
+            "831248957812239453",
+            "906610893880149131",
+            "987158034397061298",
+            "996006981039903216",
+        ];
+        for ((amount_in, reserve_in, reserve_out), expected) in CASES.iter().zip(expected) {
+            let out = get_amount_out(
+                ether(*amount_in),
+                ether(*reserve_in),
+                ether(*reserve_out),
+                30,
+            );
+            assert_eq!(out.unwrap(), U256::from_dec_str(expected).unwrap());
+        }
+        //UniswapV2Library test: getAmountOut(2, 100, 100) == 1
+        assert_eq!(
+            get_amount_out(2.into(), 100.into(), 100.into(), 30).unwrap(),
+            U256::one()
+        );
+    }
+
+    #[test]
+    fn amount_out_at_25_bps() {
+        let expected = [
+            "1663192997082117548",
+            "453512161854967037",
+            "2852037169406719085",
+            "831596498541058774",
+            "907024323709934075",
+            "987648209114086982",
+            "996505985279683515",
+        ];
+        for ((amount_in, reserve_in, reserve_out), expected) in CASES.iter().zip(expected) {
+            let out = get_amount_out(
+                ether(*amount_in),
+                ether(*reserve_in),
+                ether(*reserve_out),
+                25,
+            );
+            assert_eq!(out.unwrap(), U256::from_dec_str(expected).unwrap());
+        }
+    }
+
+    #[test]
+    fn amount_in_rounds_up() {
+        //UniswapV2Library test: getAmountIn(1, 100, 100) == 2
+        assert_eq!(
+            get_amount_in(1.into(), 100.into(), 100.into(), 30).unwrap(),
+            U256::from(2)
+        );
+        assert_eq!(
+            get_amount_in(1.into(), 100.into(), 100.into(), 25).unwrap(),
+            U256::from(2)
+        );
+        let out = U256::from_dec_str("1662497915624478906").unwrap();
+        assert_eq!(
+            get_amount_in(out, ether(5), ether(10), 30).unwrap(),
+            ether(1)
+        );
+        let out = U256::from_dec_str("453305446940074565").unwrap();
+        assert_eq!(
+            get_amount_in(out, ether(10), ether(5), 25).unwrap(),
+            U256::from_dec_str("999498746867167918").unwrap()
+        );
+    }
+
+    #[test]
+    fn amounts_along_a_path() {
+        let reserves = [(ether(5), ether(10)), (ether(10), ether(5))];
+        let amounts = get_amounts_out(ether(1), &reserves, 30).unwrap();
+        assert_eq!(
+            amounts[1],
+            U256::from_dec_str("1662497915624478906").unwrap()
+        );
+        let back = get_amounts_in(amounts[2], &reserves, 30).unwrap();
+        assert_eq!(back[2], amounts[2]);
+        assert!(back[0] <= ether(1));
+    }
+
+    #[test]
+    fn zero_amounts_and_liquidity_are_rejected() {
+        assert!(get_amount_out(U256::zero(), 100.into(), 100.into(), 30).is_err());
+        assert!(get_amount_out(1.into(), U256::zero(), 100.into(), 30).is_err());
+        assert!(get_amount_in(U256::zero(), 100.into(), 100.into(), 30).is_err());
+        assert!(get_amount_in(100.into(), 100.into(), 100.into(), 30).is_err());
+    }
+
+    #[test]
+    fn price_impact_includes_the_fee() {
+        let reserves = [(ether(1000), ether(1000))];
+        let out = get_amount_out(ether(1), ether(1000), ether(1000), 30).unwrap();
+        assert_eq!(mid_amount_out(ether(1), &reserves), ether(1));
+        assert_eq!(shortfall_bps(mid_amount_out(ether(1), &reserves), out), 39);
+    }
+}
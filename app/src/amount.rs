@@ -0,0 +1,76 @@
+use ethers::core::types::U256;
+use eyre::Result;
+
+use crate::{error::Error, utils::parse_units};
+
+// Parse an amount argument against the balance it is taken from:
+// "max", a percentage of the balance ("50%", "12.5%"), raw wei ("1500wei") or token units ("1.5")
+pub fn parse_amount(input: &str, decimals: u8, balance: U256) -> Result<U256> {
+    let input = input.trim();
+    if input.eq_ignore_ascii_case("max") {
+        return Ok(balance);
+    }
+    if let Some(percent) = input.strip_suffix('%') {
+        //percentage with up to 2 decimals, as basis points
+        let bps = parse_units(percent.trim(), 2)
+            .map_err(|_| Error::Input(format!("Invalid percentage '{}'", input)))?;
+        if bps > U256::from(10000) {
+            return Err(Error::Input(format!("Percentage '{}' is above 100%", input)).into());
+        }
+        return Ok(balance * bps / U256::from(10000));
+    }
+    parse_exact(input, decimals)
+}
+
+// "max" and percentages are taken from the balance, the other amounts are absolute
+pub fn is_relative(input: &str) -> bool {
+    let input = input.trim();
+    input.eq_ignore_ascii_case("max") || input.ends_with('%')
+}
+
+// Amount not taken from a balance: raw wei ("1500wei") or token units ("1.5")
+pub fn parse_exact(input: &str, decimals: u8) -> Result<U256> {
+    let input = input.trim();
+    if let Some(wei) = input.strip_suffix("wei") {
+        return Ok(U256::from_dec_str(wei.trim())
+            .map_err(|_| Error::Input(format!("Invalid wei amount '{}'", input)))?);
+    }
+    parse_units(input, decimals)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_relative_amounts() {
+        let balance = U256::from(1000);
+        assert_eq!(parse_amount("max", 6, balance).unwrap(), balance);
+        assert_eq!(parse_amount(" MAX ", 6, balance).unwrap(), balance);
+        assert_eq!(parse_amount("50%", 6, balance).unwrap(), U256::from(500));
+        assert_eq!(parse_amount("12.5%", 6, balance).unwrap(), U256::from(125));
+        assert_eq!(parse_amount("100%", 6, balance).unwrap(), balance);
+        //rounded down, never above the balance
+        assert_eq!(parse_amount("33.33%", 6, 1.into()).unwrap(), U256::zero());
+        assert!(parse_amount("100.01%", 6, balance).is_err());
+        assert!(parse_amount("12.345%", 6, balance).is_err());
+        assert!(parse_amount("half%", 6, balance).is_err());
+        assert!(is_relative("max") && is_relative("5%") && !is_relative("5"));
+    }
+
+    #[test]
+    fn parses_exact_amounts() {
+        let balance = U256::from(1000);
+        assert_eq!(
+            parse_amount("1500wei", 6, balance).unwrap(),
+            U256::from(1500)
+        );
+        assert_eq!(
+            parse_amount("1.5", 6, balance).unwrap(),
+            U256::from(1_500_000)
+        );
+        assert_eq!(parse_amount("1,000", 0, balance).unwrap(), U256::from(1000));
+        assert!(parse_amount("1.5wei", 6, balance).is_err());
+        assert!(parse_amount("1.1234567", 6, balance).is_err());
+    }
+}
@@ -1,5 +1,9 @@
 use clap::Args;
 
+use crate::network;
+use crate::router::Protocol;
+use crate::slippage::Slippage;
+
 ////////////////////////////////////READ////////////////////////////////////
 #[derive(Debug, Args)]
 pub struct Tokenargs {
@@ -25,6 +29,38 @@ pub struct Allowanceargs {
     #[arg(short = 's', long = "spender")]
     pub spender: String,
 }
+#[derive(Debug, Args)]
+pub struct Poolargs {
+    #[arg(short = 'a', long = "tokena")]
+    pub token_a: String,
+    #[arg(short = 'b', long = "tokenb")]
+    pub token_b: Option<String>,
+}
+#[derive(Debug, Args)]
+pub struct Twapargs {
+    #[arg(short = 'a', long = "tokena")]
+    pub token_a: String,
+    #[arg(short = 'b', long = "tokenb")]
+    pub token_b: Option<String>,
+    #[arg(short = 'w', long = "window", default_value_t = 1800)]
+    pub window: u64,
+}
+#[derive(Debug, Args)]
+pub struct Quoteargs {
+    #[arg(short = 'i', long = "intoken")]
+    pub token_in: String,
+    #[arg(short = 'o', long = "outtoken")]
+    pub token_out: String,
+    #[arg(short = 'a', long = "amount")]
+    pub amount: String,
+}
+#[derive(Debug, Args)]
+pub struct Portfolioargs {
+    #[arg(short = 'r', long = "recipient")]
+    pub recipient: Option<String>,
+    #[arg(short = 't', long = "tokens", value_delimiter = ',')]
+    pub tokens: Vec<String>,
+}
 ////////////////////////////////////TRANSACTION////////////////////////////////////
 #[derive(Debug, Args)]
 pub struct Approveargs {
@@ -41,8 +77,12 @@ pub struct Swapethargs {
     pub token_out: String,
     #[arg(short = 'a', long = "amount")]
     pub amount: String,
-    #[arg(short = 's', long = "slippage", default_value_t = 0.5)]
-    pub slippage: f64,
+    #[arg(short = 's', long = "slippage", default_value = "0.5")]
+    pub slippage: Slippage,
+    #[arg(short = 'd', long = "dex", default_value = network::DEFAULT_DEX)]
+    pub dex: String,
+    #[arg(short = 'p', long = "protocol", value_enum, default_value_t = Protocol::V2)]
+    pub protocol: Protocol,
 }
 #[derive(Debug, Args)]
 pub struct Swaptokens {
@@ -52,8 +92,96 @@ pub struct Swaptokens {
     pub token_out: String,
     #[arg(short = 'a', long = "amount")]
     pub amount: String,
-    #[arg(short = 's', long = "slippage", default_value_t = 0.5)]
-    pub slippage: f64,
+    #[arg(short = 's', long = "slippage", default_value = "0.5")]
+    pub slippage: Slippage,
+    #[arg(short = 'g', long = "twapguard")]
+    pub twap_guard: Option<f64>,
+    #[arg(short = 'w', long = "twapwindow", default_value_t = 1800)]
+    pub twap_window: u64,
+    #[arg(short = 'd', long = "dex", default_value = network::DEFAULT_DEX)]
+    pub dex: String,
+    #[arg(short = 'p', long = "protocol", value_enum, default_value_t = Protocol::V2)]
+    pub protocol: Protocol,
+}
+#[derive(Debug, Args)]
+pub struct Swaptoethargs {
+    #[arg(short = 'i', long = "intoken")]
+    pub token_in: String,
+    #[arg(short = 'a', long = "amount")]
+    pub amount: String,
+    #[arg(short = 's', long = "slippage", default_value = "0.5")]
+    pub slippage: Slippage,
+    #[arg(short = 'd', long = "dex", default_value = network::DEFAULT_DEX)]
+    pub dex: String,
+}
+#[derive(Debug, Args)]
+pub struct Swapethexactargs {
+    #[arg(short = 'o', long = "outtoken")]
+    pub token_out: String,
+    #[arg(short = 'a', long = "amountout")]
+    pub amount_out: String,
+    #[arg(short = 's', long = "slippage", default_value = "0.5")]
+    pub slippage: Slippage,
+    #[arg(short = 'd', long = "dex", default_value = network::DEFAULT_DEX)]
+    pub dex: String,
+}
+#[derive(Debug, Args)]
+pub struct Swaptokensexact {
+    #[arg(short = 'i', long = "intoken")]
+    pub token_in: String,
+    #[arg(short = 'o', long = "outtoken")]
+    pub token_out: String,
+    #[arg(short = 'a', long = "amountout")]
+    pub amount_out: String,
+    #[arg(short = 's', long = "slippage", default_value = "0.5")]
+    pub slippage: Slippage,
+    #[arg(short = 'd', long = "dex", default_value = network::DEFAULT_DEX)]
+    pub dex: String,
+}
+#[derive(Debug, Args)]
+pub struct Addliquidityargs {
+    #[arg(short = 'a', long = "tokena")]
+    pub token_a: String,
+    #[arg(short = 'b', long = "tokenb")]
+    pub token_b: String,
+    #[arg(short = 'x', long = "amounta")]
+    pub amount_a: String,
+    #[arg(short = 'y', long = "amountb")]
+    pub amount_b: String,
+    #[arg(short = 's', long = "slippage", default_value = "0.5")]
+    pub slippage: Slippage,
+    #[arg(short = 'd', long = "dex", default_value = network::DEFAULT_DEX)]
+    pub dex: String,
+}
+#[derive(Debug, Args)]
+pub struct Addliquidityethargs {
+    #[arg(short = 'i', long = "intoken")]
+    pub token: String,
+    #[arg(short = 'a', long = "amount")]
+    pub amount: String,
+    #[arg(short = 'e', long = "amounteth")]
+    pub amount_eth: String,
+    #[arg(short = 's', long = "slippage", default_value = "0.5")]
+    pub slippage: Slippage,
+    #[arg(short = 'd', long = "dex", default_value = network::DEFAULT_DEX)]
+    pub dex: String,
+}
+#[derive(Debug, Args)]
+pub struct Removeliquidityargs {
+    #[arg(short = 'a', long = "tokena")]
+    pub token_a: String,
+    #[arg(short = 'b', long = "tokenb")]
+    pub token_b: String,
+    #[arg(short = 'l', long = "liquidity")]
+    pub liquidity: String,
+    #[arg(short = 's', long = "slippage", default_value = "0.5")]
+    pub slippage: Slippage,
+    #[arg(short = 'e', long = "eth")]
+    pub eth: bool,
+    #[arg(short = 'p', long = "permit")]
+    pub permit: bool,
+    #[arg(short = 'd', long = "dex", default_value = network::DEFAULT_DEX)]
+    pub dex: String,
 }
 #[derive(Debug, Args)]
 pub struct Transfereth {
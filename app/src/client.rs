@@ -7,27 +7,64 @@ use ethers::{
 };
 use eyre::Result;
 use std::env;
+use std::sync::atomic::{AtomicBool, Ordering};
 use std::sync::Arc;
-#[path = "alias.rs"]
-mod alias;
+
+use crate::error::Error;
+use crate::network;
+use crate::output::{self, text};
+use crate::settings;
+
+// Variable from .env, a configuration error when missing
+fn env_var(name: &str) -> Result<String, Error> {
+    dotenv::dotenv().ok();
+    env::var(name).map_err(|_| Error::Config(format!("{} is not set in .env", name)))
+}
+
+static CHAIN_CHECKED: AtomicBool = AtomicBool::new(false);
+
+// Provider for the RPC of the network profile, checked once against its chain id
+async fn get_provider() -> Result<Provider<Http>, Error> {
+    let network = network::get();
+    let rpc = if network.rpc.is_empty() {
+        env_var("RPC")?
+    } else {
+        network.rpc.clone()
+    };
+    let provider = Provider::<Http>::try_from(rpc.as_str())
+        .map_err(|e| Error::Config(format!("invalid RPC url {}: {}", rpc, e)))?;
+
+    if !CHAIN_CHECKED.load(Ordering::SeqCst) {
+        let chain_id = provider
+            .get_chainid()
+            .await
+            .map_err(|e| Error::Rpc(e.to_string()))?
+            .as_u64();
+        if chain_id != network.chain_id {
+            return Err(Error::Config(format!(
+                "RPC is on chain {} but network {} expects chain {}",
+                chain_id, network.name, network.chain_id
+            )));
+        }
+        CHAIN_CHECKED.store(true, Ordering::SeqCst);
+    }
+
+    Ok(provider)
+}
 
 pub async fn get_client() -> Result<Arc<Provider<Http>>> {
     //config
-    dotenv::dotenv().ok();
-    let client = Provider::<Http>::try_from(&env::var("RPC").unwrap())?;
-    let client = Arc::new(client);
+    let client = Arc::new(get_provider().await?);
 
     Ok(Arc::clone(&client))
 }
 pub async fn get_client_signed() -> Result<Arc<SignerMiddleware<Provider<Http>, Wallet<SigningKey>>>>
 {
-    dotenv::dotenv().ok();
-    let provider = Provider::<Http>::try_from(env::var("RPC").unwrap())?;
-    let chain_id = provider.get_chainid().await?;
-    let wallet = env::var("PRIVATE_KEY")
-        .unwrap()
-        .parse::<LocalWallet>()?
-        .with_chain_id(chain_id.as_u64());
+    let provider = get_provider().await?;
+    let wallet = env_var("PRIVATE_KEY")?
+        .parse::<LocalWallet>()
+        .map_err(|_| Error::Config("PRIVATE_KEY is not a valid private key".to_string()))?
+        .with_chain_id(network::get().chain_id);
     let client: SignerMiddleware<
         Provider<Http>,
         ethers::signers::Wallet<ethers::core::k256::ecdsa::SigningKey>,
@@ -41,12 +78,25 @@ pub async fn print_state(
 ) -> Result<()> {
     //print state Account/Bc
     let account = provider_signed.address();
-    let block_number = provider_signed.get_block_number().await?;
-    let nonce = provider_signed.get_transaction_count(account, None).await?;
-    println!("{}", "\n===== INFOS =====\n".bold().magenta());
-    println!("Account: {:?}", account);
-    println!("Block: {:?}", block_number);
-    println!("Gas price: {:?}", provider_signed.get_gas_price().await?);
-    println!("Nonce: {:?}", nonce);
+    //dry runs read the state at --block
+    let block_number = match settings::get().block {
+        Some(block) => block.into(),
+        None => provider_signed.get_block_number().await?,
+    };
+    let nonce = provider_signed
+        .get_transaction_count(account, settings::block_id())
+        .await?;
+    let gas_price = provider_signed.get_gas_price().await?;
+    output::field("network", &network::get().name);
+    output::field("account", account);
+    output::field("block", block_number.as_u64());
+    output::field("nonce", nonce.as_u64());
+    output::field("gasPrice", gas_price.to_string());
+    text!("{}", "\n===== INFOS =====\n".bold().magenta());
+    text!("Network: {}", network::get().name);
+    text!("Account: {:?}", account);
+    text!("Block: {:?}", block_number);
+    text!("Gas price: {:?}", gas_price);
+    text!("Nonce: {:?}", nonce);
     Ok(())
 }
@@ -0,0 +1,165 @@
+use ethers::{
+    core::types::{BlockId, BlockNumber, U256},
+    providers::Middleware,
+};
+use eyre::{eyre, Result};
+use std::fmt;
+use std::str::FromStr;
+use std::sync::{Arc, OnceLock};
+
+use crate::{
+    error::Error,
+    output::{self, text},
+    settings,
+};
+
+// Duration given as "90s", "5m", "1h" or seconds, used for the deadline and the base fee wait
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Duration(u64);
+
+impl Duration {
+    pub fn seconds(self) -> u64 {
+        self.0
+    }
+}
+
+impl Default for Duration {
+    fn default() -> Self {
+        Duration(300)
+    }
+}
+
+impl FromStr for Duration {
+    type Err = String;
+
+    fn from_str(input: &str) -> Result<Self, Self::Err> {
+        let input = input.trim();
+        let (value, unit) = match input.find(|c: char| !c.is_ascii_digit()) {
+            Some(i) => input.split_at(i),
+            None => (input, "s"),
+        };
+        let multiplier = match unit.trim() {
+            "s" => 1,
+            "m" => 60,
+            "h" => 3600,
+            _ => return Err(invalid(input)),
+        };
+        let value: u64 = value.parse().map_err(|_| invalid(input))?;
+        if value == 0 {
+            return Err(format!("duration '{}' must be above 0", input));
+        }
+        value
+            .checked_mul(multiplier)
+            .map(Duration)
+            .ok_or_else(|| invalid(input))
+    }
+}
+
+fn invalid(input: &str) -> String {
+    format!("invalid duration '{}', expected 90s, 5m or 1h", input)
+}
+
+impl fmt::Display for Duration {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}s", self.0)
+    }
+}
+
+// Deadline of the transactions of the command, computed once
+static DEADLINE: OnceLock<u64> = OnceLock::new();
+
+// Timestamp of the reference block (--block or latest) plus --deadline, in seconds like
+// block.timestamp in the router
+pub async fn get<M: Middleware + 'static>(client: &Arc<M>) -> Result<U256> {
+    if let Some(deadline) = DEADLINE.get() {
+        return Ok(U256::from(*deadline));
+    }
+    let block = settings::block_id().unwrap_or(BlockId::Number(BlockNumber::Latest));
+    let timestamp = client
+        .get_block(block)
+        .await
+        .map_err(|e| Error::Rpc(e.to_string()))?
+        .ok_or_else(|| eyre!("Block {:?} not found", block))?
+        .timestamp
+        .as_u64();
+    let deadline = timestamp
+        .checked_add(settings::get().deadline.seconds())
+        .ok_or_else(|| {
+            Error::Config(format!(
+                "--deadline {} is too far",
+                settings::get().deadline
+            ))
+        })?;
+    let deadline = *DEADLINE.get_or_init(|| deadline);
+    output::field("deadline", deadline);
+    text!(
+        "Deadline: {} (block timestamp {} + {})",
+        deadline,
+        timestamp,
+        settings::get().deadline
+    );
+    Ok(U256::from(deadline))
+}
+
+// Revert reasons of the V2 and V3 routers for an expired deadline
+pub fn is_expired(reason: &str) -> bool {
+    reason.contains("EXPIRED") || reason.contains("Transaction too old")
+}
+
+// Error for a transaction mined (or simulated) after the deadline
+pub fn expired_error(timestamp: Option<u64>) -> Error {
+    let deadline = DEADLINE.get().copied().unwrap_or_default();
+    match timestamp {
+        Some(timestamp) => Error::Revert(format!(
+            "deadline expired: included at {} but the deadline was {} ({} late), retry or raise --deadline (currently {})",
+            timestamp,
+            deadline,
+            timestamp.saturating_sub(deadline),
+            settings::get().deadline
+        )),
+        None => Error::Revert(format!(
+            "deadline {} expired before the transaction was included, retry or raise --deadline (currently {})",
+            deadline,
+            settings::get().deadline
+        )),
+    }
+}
+
+// Whether a transaction mined at this timestamp was past the deadline of the command
+pub fn was_expired(timestamp: u64) -> bool {
+    DEADLINE.get().is_some_and(|deadline| timestamp > *deadline)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn seconds(input: &str) -> u64 {
+        input.parse::<Duration>().unwrap().seconds()
+    }
+
+    #[test]
+    fn parses_units() {
+        assert_eq!(seconds("90s"), 90);
+        assert_eq!(seconds("5m"), 300);
+        assert_eq!(seconds("1h"), 3600);
+        assert_eq!(seconds("120"), 120);
+        assert_eq!(seconds(" 2 m "), 120);
+        assert_eq!(Duration::default().seconds(), 300);
+    }
+
+    #[test]
+    fn rejects_invalid_durations() {
+        for input in ["0", "0s", "", "s", "5d", "-5m", "1.5h", "m5"] {
+            assert!(input.parse::<Duration>().is_err(), "{} accepted", input);
+        }
+    }
+
+    #[test]
+    fn rejects_overflow() {
+        assert_eq!(seconds("18446744073709551615s"), u64::MAX);
+        assert!("18446744073709551616".parse::<Duration>().is_err());
+        assert!("307445734561825861m".parse::<Duration>().is_err());
+        assert!("9999999999999999h".parse::<Duration>().is_err());
+    }
+}
@@ -0,0 +1,106 @@
+use colored::Colorize;
+use ethers::{
+    contract::ContractError,
+    core::k256::ecdsa::SigningKey,
+    middleware::SignerMiddleware,
+    providers::{Http, Provider, ProviderError},
+    signers::Wallet,
+};
+use thiserror::Error;
+
+use crate::{output, sender::revert_reason};
+
+// Failures a command can end with, each one has its own process exit code
+#[derive(Debug, Clone, Error)]
+pub enum Error {
+    #[error("Configuration error: {0}")]
+    Config(String),
+    #[error("Unknown token or address: {0}")]
+    Alias(String),
+    #[error("RPC error: {0}")]
+    Rpc(String),
+    #[error("Transaction reverted: {0}")]
+    Revert(String),
+    #[error("Insufficient balance: {0}")]
+    InsufficientBalance(String),
+    #[error("Transaction cancelled: {0}")]
+    Aborted(String),
+    #[error("Invalid input: {0}")]
+    Input(String),
+    #[error("{0}")]
+    Other(String),
+}
+
+impl Error {
+    pub fn exit_code(&self) -> i32 {
+        match self {
+            Error::Other(_) => 1,
+            Error::Config(_) => 2,
+            Error::Alias(_) => 3,
+            Error::Rpc(_) => 4,
+            Error::Revert(_) => 5,
+            Error::InsufficientBalance(_) => 6,
+            Error::Aborted(_) => 7,
+            Error::Input(_) => 8,
+        }
+    }
+
+    pub fn kind(&self) -> &'static str {
+        match self {
+            Error::Other(_) => "other",
+            Error::Config(_) => "config",
+            Error::Alias(_) => "alias",
+            Error::Rpc(_) => "rpc",
+            Error::Revert(_) => "revert",
+            Error::InsufficientBalance(_) => "insufficient_balance",
+            Error::Aborted(_) => "aborted",
+            Error::Input(_) => "input",
+        }
+    }
+
+    // Typed error behind a report, errors coming from ethers are sorted into revert and RPC errors
+    pub fn from_report(report: &eyre::Report) -> Error {
+        if let Some(error) = report.downcast_ref::<Error>() {
+            return error.clone();
+        }
+
+        let message = report.to_string();
+        if message.contains("revert") {
+            return Error::from_rpc(message);
+        }
+        let from_rpc = report.downcast_ref::<ProviderError>().is_some()
+            || report
+                .downcast_ref::<ContractError<Provider<Http>>>()
+                .is_some()
+            || report
+                .downcast_ref::<ContractError<SignerMiddleware<Provider<Http>, Wallet<SigningKey>>>>()
+                .is_some();
+        if from_rpc {
+            Error::Rpc(message)
+        } else {
+            Error::Other(message)
+        }
+    }
+
+    // Error returned by the node, a revert when it carries a revert reason
+    pub fn from_rpc(message: String) -> Error {
+        if message.contains("revert") {
+            Error::Revert(revert_reason(&message))
+        } else {
+            Error::Rpc(message)
+        }
+    }
+}
+
+// Print the error with its causes and exit with the code of its kind
+pub fn exit_with(report: eyre::Report) -> ! {
+    let error = Error::from_report(&report);
+    let causes: Vec<String> = report.chain().skip(1).map(|c| c.to_string()).collect();
+    if !output::print_error(&error, &causes) {
+        eprintln!("\n{} {}", "Error:".red().bold(), error);
+        for cause in causes {
+            eprintln!("  {} {}", "Caused by:".red(), cause);
+        }
+    }
+    std::process::exit(error.exit_code());
+}
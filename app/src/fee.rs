@@ -0,0 +1,372 @@
+use clap::ValueEnum;
+use colored::Colorize;
+use ethers::{
+    core::types::{
+        transaction::eip2718::TypedTransaction, BlockNumber, Eip1559TransactionRequest,
+        TransactionRequest, U256,
+    },
+    providers::{Middleware, StreamExt},
+};
+use eyre::{eyre, Result};
+use serde_json::json;
+use std::fmt;
+use std::str::FromStr;
+use std::sync::{Arc, OnceLock};
+use std::time::Instant;
+
+use crate::{
+    deadline,
+    error::Error,
+    loader::{set_loader_message, start_loader, stop_loader},
+    output::{self, text},
+    settings,
+    utils::{parse_units, to_human_readable},
+};
+
+// Blocks of eth_feeHistory the priority fee is taken from
+const HISTORY_BLOCKS: u64 = 10;
+
+// Fee presets, from the cheapest to the fastest inclusion
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, ValueEnum)]
+pub enum Speed {
+    Slow,
+    #[default]
+    Normal,
+    Fast,
+    Urgent,
+}
+
+impl Speed {
+    // Percentile of the priority fees paid in the recent blocks
+    fn reward_percentile(self) -> f64 {
+        match self {
+            Speed::Slow => 10.0,
+            Speed::Normal => 50.0,
+            Speed::Fast => 75.0,
+            Speed::Urgent => 90.0,
+        }
+    }
+
+    // Max fee over the next base fee in percent, the room left for the base fee to rise
+    // (12.5 % per full block) before the transaction is priced out
+    fn base_fee_percent(self) -> u64 {
+        match self {
+            Speed::Slow => 125,
+            Speed::Normal => 200,
+            Speed::Fast => 200,
+            Speed::Urgent => 300,
+        }
+    }
+}
+
+// Fee given in gwei with up to 9 decimals ("30", "1.5")
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Gwei(U256);
+
+impl Gwei {
+    pub fn wei(self) -> U256 {
+        self.0
+    }
+}
+
+impl FromStr for Gwei {
+    type Err = String;
+
+    fn from_str(input: &str) -> Result<Self, Self::Err> {
+        parse_units(input, 9)
+            .map(Gwei)
+            .map_err(|_| format!("invalid fee '{}', expected gwei like 30 or 1.5", input))
+    }
+}
+
+impl fmt::Display for Gwei {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} gwei", to_human_readable(self.0, 9))
+    }
+}
+
+// Fee strategy of the command: preset, overrides and cap from the command line
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Strategy {
+    pub speed: Speed,
+    pub max_fee: Option<Gwei>,
+    pub priority_fee: Option<Gwei>,
+    pub cap: Option<Gwei>,
+}
+
+// Fees of a transaction, chains without EIP-1559 only take a gas price
+#[derive(Debug, Clone, Copy)]
+pub enum Fees {
+    Eip1559 { max_fee: U256, priority_fee: U256 },
+    Legacy { gas_price: U256 },
+}
+
+impl Fees {
+    // Highest price paid per gas
+    pub fn max_fee(&self) -> U256 {
+        match self {
+            Fees::Eip1559 { max_fee, .. } => *max_fee,
+            Fees::Legacy { gas_price } => *gas_price,
+        }
+    }
+}
+
+// Fees of the --fee preset from eth_feeHistory, with --max-fee/--priority-fee overrides and
+// capped by --fee-cap
+pub async fn get<M: Middleware + 'static>(client: &Arc<M>) -> Result<Fees> {
+    let strategy = settings::get().fee;
+    let last_block = settings::get()
+        .block
+        .map_or(BlockNumber::Latest, |b| BlockNumber::Number(b.into()));
+    let history = client
+        .fee_history(
+            HISTORY_BLOCKS,
+            last_block,
+            &[strategy.speed.reward_percentile()],
+        )
+        .await
+        .ok();
+    //the last base fee of the history is the one of the next block, none before EIP-1559
+    let base_fee = history
+        .as_ref()
+        .and_then(|h| h.base_fee_per_gas.last().copied())
+        .filter(|base_fee| !base_fee.is_zero());
+
+    let fees = match (history, base_fee) {
+        (Some(history), Some(base_fee)) => {
+            let mut rewards: Vec<U256> = history
+                .reward
+                .iter()
+                .filter_map(|r| r.first().copied())
+                .collect();
+            rewards.sort();
+            let priority_fee = match strategy.priority_fee {
+                Some(priority_fee) => priority_fee.wei(),
+                None => rewards.get(rewards.len() / 2).copied().unwrap_or_default(),
+            };
+            let max_fee = match strategy.max_fee {
+                Some(max_fee) => max_fee.wei(),
+                None => base_fee * strategy.speed.base_fee_percent() / 100 + priority_fee,
+            };
+            let max_fee = cap(max_fee, Some(base_fee));
+            Fees::Eip1559 {
+                max_fee,
+                //the tip is paid out of the max fee
+                priority_fee: priority_fee.min(max_fee),
+            }
+        }
+        _ => {
+            let gas_price = match strategy.max_fee {
+                Some(max_fee) => max_fee.wei(),
+                None => client
+                    .get_gas_price()
+                    .await
+                    .map_err(|e| Error::Rpc(e.to_string()))?,
+            };
+            Fees::Legacy {
+                gas_price: cap(gas_price, None),
+            }
+        }
+    };
+    Ok(fees)
+}
+
+// Set once the fee cap warning is printed, the fees are fetched for every transaction sent
+static CAP_WARNED: OnceLock<()> = OnceLock::new();
+
+// Max fee lowered to --fee-cap, a cap under the base fee leaves the transaction pending
+fn cap(max_fee: U256, base_fee: Option<U256>) -> U256 {
+    match settings::get().fee.cap {
+        Some(cap) if cap.wei() < max_fee => {
+            let below_base_fee = base_fee.filter(|base_fee| cap.wei() < *base_fee);
+            if let Some(base_fee) = below_base_fee.filter(|_| CAP_WARNED.set(()).is_ok()) {
+                text!(
+                    "{} fee cap {} is below the current base fee of {} gwei, the transaction waits until the base fee drops",
+                    "Warning:".yellow().bold(),
+                    cap,
+                    to_human_readable(base_fee, 9)
+                );
+            }
+            cap.wei()
+        }
+        _ => max_fee,
+    }
+}
+
+// Set the fees of the strategy on the transaction, as an EIP-1559 transaction unless the chain
+// has no base fee
+pub async fn apply<M: Middleware + 'static>(
+    client: &Arc<M>,
+    tx: &mut TypedTransaction,
+) -> Result<Fees> {
+    let fees = get(client).await?;
+    match fees {
+        Fees::Eip1559 {
+            max_fee,
+            priority_fee,
+        } => {
+            //contract calls are built as legacy transactions (ethers "legacy" feature), which
+            //would pay the whole max fee as gas price
+            if let TypedTransaction::Legacy(request) = tx {
+                *tx = TypedTransaction::Eip1559(Eip1559TransactionRequest {
+                    from: request.from,
+                    to: request.to.clone(),
+                    gas: request.gas,
+                    value: request.value,
+                    data: request.data.clone(),
+                    nonce: request.nonce,
+                    chain_id: request.chain_id,
+                    ..Default::default()
+                });
+            }
+            if let TypedTransaction::Eip1559(request) = tx {
+                request.max_fee_per_gas = Some(max_fee);
+                request.max_priority_fee_per_gas = Some(priority_fee);
+            }
+        }
+        Fees::Legacy { gas_price } => {
+            if let TypedTransaction::Eip1559(request) = tx {
+                let legacy: TransactionRequest = request.clone().into();
+                *tx = TypedTransaction::Legacy(legacy);
+            }
+            tx.set_gas_price(gas_price);
+        }
+    }
+    print_fees(&fees);
+    Ok(fees)
+}
+
+// One entry per transaction sent, in the order of the receipts
+pub fn print_fees(fees: &Fees) {
+    let speed = settings::get().fee.speed;
+    match fees {
+        Fees::Eip1559 {
+            max_fee,
+            priority_fee,
+        } => {
+            output::push(
+                "fees",
+                json!({
+                    "speed": format!("{:?}", speed).to_lowercase(),
+                    "maxFee": max_fee.to_string(),
+                    "priorityFee": priority_fee.to_string(),
+                }),
+            );
+            text!(
+                "{} max {} gwei, priority {} gwei ({:?})",
+                "Fees:".bold(),
+                to_human_readable(*max_fee, 9),
+                to_human_readable(*priority_fee, 9),
+                speed
+            );
+        }
+        Fees::Legacy { gas_price } => {
+            output::push(
+                "fees",
+                json!({
+                    "speed": format!("{:?}", speed).to_lowercase(),
+                    "gasPrice": gas_price.to_string(),
+                }),
+            );
+            text!(
+                "{} gas price {} gwei (legacy)",
+                "Fees:".bold(),
+                to_human_readable(*gas_price, 9)
+            );
+        }
+    }
+}
+
+// Hold the transaction until a new block has a base fee at or below --max-base-fee, for at most
+// --max-base-fee-timeout and never past the deadline of the command
+pub async fn wait_for_base_fee<M: Middleware + 'static>(client: &Arc<M>) -> Result<()> {
+    let Some(threshold) = settings::get().max_base_fee else {
+        return Ok(());
+    };
+    let block = client
+        .get_block(BlockNumber::Latest)
+        .await
+        .map_err(|e| Error::Rpc(e.to_string()))?
+        .ok_or_else(|| eyre!("Latest block not found"))?;
+    let Some(mut base_fee) = block.base_fee_per_gas else {
+        text!(
+            "{} the chain has no base fee, --max-base-fee is ignored",
+            "Warning:".yellow().bold()
+        );
+        return Ok(());
+    };
+
+    let timeout = settings::get().max_base_fee_timeout;
+    let start = Instant::now();
+    let mut blocks = 0;
+    if base_fee > threshold.wei() {
+        let loader = start_loader(&waiting_message(base_fee, threshold));
+        //new blocks are polled with a filter, the provider is HTTP
+        let wait = async {
+            let mut stream = client
+                .watch_blocks()
+                .await
+                .map_err(|e| Error::Rpc(e.to_string()))?;
+            while let Some(hash) = stream.next().await {
+                let Some(block) = client
+                    .get_block(hash)
+                    .await
+                    .map_err(|e| Error::Rpc(e.to_string()))?
+                else {
+                    continue;
+                };
+                blocks += 1;
+                base_fee = block.base_fee_per_gas.unwrap_or_default();
+                set_loader_message(&loader, &waiting_message(base_fee, threshold));
+                if deadline::was_expired(block.timestamp.as_u64()) {
+                    return Err(Error::Aborted(format!(
+                        "deadline reached while waiting for the base fee, raise --deadline (currently {})",
+                        settings::get().deadline
+                    )));
+                }
+                if base_fee <= threshold.wei() {
+                    return Ok(());
+                }
+            }
+            Err(Error::Rpc("block filter stopped".to_string()))
+        };
+        let waited = tokio::time::timeout(std::time::Duration::from_secs(timeout.seconds()), wait)
+            .await
+            .unwrap_or_else(|_| {
+                Err(Error::Aborted(format!(
+                    "base fee still {} gwei after {}, above --max-base-fee {}",
+                    to_human_readable(base_fee, 9),
+                    timeout,
+                    threshold
+                )))
+            });
+        stop_loader(loader);
+        waited?;
+    }
+
+    output::push(
+        "baseFeeWait",
+        json!({
+            "maxBaseFee": threshold.wei().to_string(),
+            "baseFee": base_fee.to_string(),
+            "blocks": blocks,
+            "seconds": start.elapsed().as_secs(),
+        }),
+    );
+    text!(
+        "{} {} gwei (max {}), waited {} blocks",
+        "Base fee:".bold(),
+        to_human_readable(base_fee, 9),
+        threshold,
+        blocks
+    );
+    Ok(())
+}
+
+fn waiting_message(base_fee: U256, threshold: Gwei) -> String {
+    format!(
+        "Waiting for the base fee: {} gwei, max {}...",
+        to_human_readable(base_fee, 9),
+        threshold
+    )
+}
@@ -0,0 +1,81 @@
+use colored::Colorize;
+use ethers::{
+    core::types::{transaction::eip2718::TypedTransaction, U256},
+    providers::Middleware,
+};
+use eyre::Result;
+use serde_json::json;
+use std::sync::Arc;
+
+use crate::{
+    error::Error,
+    fee,
+    output::{self, text},
+    settings,
+    utils::to_human_readable,
+};
+
+// ETH kept for the gas of a transaction sending "max" or a percentage of the balance as value
+#[derive(Debug, Clone, Copy)]
+pub struct GasReserve {
+    pub gas: U256,
+    pub max_fee: U256,
+    pub amount: U256,
+}
+
+// Gas of this transaction times the max fee of the --fee strategy, plus --gas-margin percent
+pub async fn reserve<M: Middleware + 'static>(
+    client: &Arc<M>,
+    tx: &TypedTransaction,
+) -> Result<GasReserve> {
+    let gas = client
+        .estimate_gas(tx, settings::block_id())
+        .await
+        .map_err(|e| Error::from_rpc(e.to_string()))?;
+    with_gas(client, gas).await
+}
+
+// Same as reserve with a fixed gas limit when the transaction cannot be estimated yet, like a
+// transfer of tokens not approved so far
+pub async fn reserve_or<M: Middleware + 'static>(
+    client: &Arc<M>,
+    tx: &TypedTransaction,
+    fallback_gas: u64,
+) -> Result<GasReserve> {
+    match client.estimate_gas(tx, settings::block_id()).await {
+        Ok(gas) => with_gas(client, gas).await,
+        Err(_) => with_gas(client, U256::from(fallback_gas)).await,
+    }
+}
+
+async fn with_gas<M: Middleware + 'static>(client: &Arc<M>, gas: U256) -> Result<GasReserve> {
+    let max_fee = fee::get(client).await?.max_fee();
+    //in U256, a huge --gas-margin cannot overflow
+    let percent = U256::from(100) + U256::from(settings::get().gas_margin);
+    let amount = gas * max_fee * percent / U256::from(100);
+    Ok(GasReserve {
+        gas,
+        max_fee,
+        amount,
+    })
+}
+
+pub fn print_reserve(reserve: &GasReserve) {
+    output::field(
+        "gasReserve",
+        json!({
+            "gas": reserve.gas.to_string(),
+            "maxFee": reserve.max_fee.to_string(),
+            "marginPercent": settings::get().gas_margin,
+            "amount": output::amount(reserve.amount, 18),
+        }),
+    );
+    text!(
+        "{} {} ETH ({} gas at {} gwei + {} %)",
+        "Reserved for gas:".bold(),
+        to_human_readable(reserve.amount, 18),
+        reserve.gas,
+        to_human_readable(reserve.max_fee, 9),
+        settings::get().gas_margin
+    );
+}
@@ -0,0 +1,674 @@
+use colored::Colorize;
+use ethers::{
+    abi::{encode, Token},
+    contract::abigen,
+    core::types::{Address, H256, U256},
+    prelude::k256::ecdsa::SigningKey,
+    providers::Middleware,
+    signers::{Signer, Wallet},
+    utils::keccak256,
+};
+use eyre::Result;
+use serde_json::json;
+use std::sync::Arc;
+abigen!(IUniswapPair, "./abi/lp_abi.json");
+
+// Gas kept for addLiquidityETH when it cannot be estimated before the token approval, enough
+// for the creation of the pair
+const ADD_LIQUIDITY_ETH_GAS: u64 = 4_000_000;
+
+use crate::{
+    alias,
+    amount::{is_relative, parse_amount},
+    client::{get_client_signed, print_state},
+    deadline,
+    error::Error,
+    gas::{self, print_reserve},
+    network::{self, Dex},
+    output::{self, text},
+    router::get_pair,
+    sender::{print_receipt, send_call, Delta},
+    settings,
+    slippage::Slippage,
+    tx::{approve_if_needed, check_balance, IUniswapRouter, IERC20},
+    utils::{at_block, decode_logs, get_token_metadata, to_human_readable},
+};
+
+// Reserves of the pair ordered as (token_a, other token)
+pub async fn get_reserves<M: Middleware + 'static>(
+    pair: &IUniswapPair<M>,
+    token_a: Address,
+) -> Result<(U256, U256)> {
+    let (reserve_0, reserve_1, _) = at_block(pair.get_reserves()).call().await?;
+    let token_0 = pair.token_0().call().await?;
+    if token_0 == token_a {
+        Ok((U256::from(reserve_0), U256::from(reserve_1)))
+    } else {
+        Ok((U256::from(reserve_1), U256::from(reserve_0)))
+    }
+}
+
+// Same logic as the router: keep amount_a and quote amount_b, or the other way around
+async fn optimal_amounts<M: Middleware + 'static>(
+    client: Arc<M>,
+    dex: &Dex,
+    token_a: Address,
+    token_b: Address,
+    desired_a: U256,
+    desired_b: U256,
+) -> Result<(U256, U256)> {
+    let pair_addr = get_pair(client.clone(), dex, token_a, token_b).await?;
+    if pair_addr.is_zero() {
+        text!(
+            "{}",
+            "No pool for this pair yet, it will be created at the given ratio.".yellow()
+        );
+        return Ok((desired_a, desired_b));
+    }
+
+    let pair = IUniswapPair::new(pair_addr, client.clone());
+    let (reserve_a, reserve_b) = get_reserves(&pair, token_a).await?;
+    if reserve_a.is_zero() && reserve_b.is_zero() {
+        return Ok((desired_a, desired_b));
+    }
+
+    let router = IUniswapRouter::new(dex.router, client);
+    let amount_b_optimal = at_block(router.quote(desired_a, reserve_a, reserve_b))
+        .call()
+        .await?;
+    if amount_b_optimal <= desired_b {
+        return Ok((desired_a, amount_b_optimal));
+    }
+    let amount_a_optimal = at_block(router.quote(desired_b, reserve_b, reserve_a))
+        .call()
+        .await?;
+    Ok((amount_a_optimal, desired_b))
+}
+
+#[tokio::main]
+pub async fn add_liquidity(
+    token_a_a: &String,
+    token_b_a: &String,
+    amount_a: &str,
+    amount_b: &str,
+    slippage: &Slippage,
+    dex: &str,
+) -> Result<()> {
+    let client_signed = get_client_signed().await?;
+    let account = client_signed.address();
+    print_state(&client_signed).await?;
+
+    text!("{}", "\n===== ADD LIQUIDITY =====\n".bold().blue());
+
+    //Check for alias params
+    let token_a = alias::alias_token(token_a_a)?;
+    let token_b = alias::alias_token(token_b_a)?;
+    let (decimals_a, symbol_a, _) = get_token_metadata(token_a_a).await?;
+    let (decimals_b, symbol_b, _) = get_token_metadata(token_b_a).await?;
+
+    //params
+    let dex = network::dex(dex)?;
+    let router_addr = dex.router;
+    let router = IUniswapRouter::new(router_addr, client_signed.clone());
+    let token_a_contract = IERC20::new(token_a, client_signed.clone());
+    let token_b_contract = IERC20::new(token_b, client_signed.clone());
+
+    let deadline = deadline::get(&client_signed).await?;
+
+    let balance_a = at_block(token_a_contract.balance_of(account))
+        .call()
+        .await?;
+    let balance_b = at_block(token_b_contract.balance_of(account))
+        .call()
+        .await?;
+    text!(
+        "{} {} {} / {} {}",
+        "Actual Balance:".bold(),
+        to_human_readable(balance_a, decimals_a),
+        symbol_a,
+        to_human_readable(balance_b, decimals_b),
+        symbol_b
+    );
+
+    let desired_a = parse_amount(amount_a, decimals_a, balance_a)?;
+    let desired_b = parse_amount(amount_b, decimals_b, balance_b)?;
+
+    check_balance(balance_a, desired_a)?;
+    check_balance(balance_b, desired_b)?;
+
+    let (amount_a_in, amount_b_in) = optimal_amounts(
+        client_signed.clone(),
+        dex,
+        token_a,
+        token_b,
+        desired_a,
+        desired_b,
+    )
+    .await?;
+
+    let amount_a_min = slippage.min_out(amount_a_in);
+    let amount_b_min = slippage.min_out(amount_b_in);
+
+    output::field("slippageBps", slippage.bps());
+    output::field(
+        "tokenA",
+        json!({
+            "address": token_a,
+            "symbol": symbol_a,
+            "amount": output::amount(amount_a_in, decimals_a),
+            "min": output::amount(amount_a_min, decimals_a),
+        }),
+    );
+    output::field(
+        "tokenB",
+        json!({
+            "address": token_b,
+            "symbol": symbol_b,
+            "amount": output::amount(amount_b_in, decimals_b),
+            "min": output::amount(amount_b_min, decimals_b),
+        }),
+    );
+    text!("Slippage: {}", slippage);
+    text!(
+        "Add {} {} and {} {} (min {} {} / {} {})...",
+        to_human_readable(amount_a_in, decimals_a),
+        symbol_a,
+        to_human_readable(amount_b_in, decimals_b),
+        symbol_b,
+        to_human_readable(amount_a_min, decimals_a),
+        symbol_a,
+        to_human_readable(amount_b_min, decimals_b),
+        symbol_b
+    );
+
+    //check allowances and approve if necessary
+    approve_if_needed(
+        &token_a_contract,
+        account,
+        router_addr,
+        amount_a_in,
+        decimals_a,
+        &symbol_a,
+    )
+    .await?;
+    approve_if_needed(
+        &token_b_contract,
+        account,
+        router_addr,
+        amount_b_in,
+        decimals_b,
+        &symbol_b,
+    )
+    .await?;
+
+    let receipt_add = send_call(
+        &client_signed,
+        router.add_liquidity(
+            token_a,
+            token_b,
+            amount_a_in,
+            amount_b_in,
+            amount_a_min,
+            amount_b_min,
+            account,
+            deadline,
+        ),
+        "Adding liquidity...",
+        &[
+            Delta::spend(&symbol_a, decimals_a, amount_a_in),
+            Delta::spend(&symbol_b, decimals_b, amount_b_in),
+        ],
+    )
+    .await?;
+
+    let pair_addr = get_pair(client_signed.clone(), dex, token_a, token_b).await?;
+    print_minted(&receipt_add, pair_addr, account)?;
+
+    Ok(())
+}
+
+#[tokio::main]
+pub async fn add_liquidity_eth(
+    token_a: &String,
+    amount_token: &str,
+    amount_eth: &str,
+    slippage: &Slippage,
+    dex: &str,
+) -> Result<()> {
+    let client_signed = get_client_signed().await?;
+    let account = client_signed.address();
+    print_state(&client_signed).await?;
+
+    text!("{}", "\n===== ADD LIQUIDITY ETH =====\n".bold().blue());
+
+    //Check for alias params
+    let token = alias::alias_token(token_a)?;
+    let (decimals_token, symbol_token, _) = get_token_metadata(token_a).await?;
+
+    //params
+    let dex = network::dex(dex)?;
+    let router_addr = dex.router;
+    let router = IUniswapRouter::new(router_addr, client_signed.clone());
+    let weth = network::get().weth;
+    let token_contract = IERC20::new(token, client_signed.clone());
+
+    let deadline = deadline::get(&client_signed).await?;
+
+    let balance_of = at_block(token_contract.balance_of(account)).call().await?;
+    let eth_balance = client_signed
+        .get_balance(account, settings::block_id())
+        .await?;
+    text!(
+        "{} {} {} / {} ETH",
+        "Actual Balance:".bold(),
+        to_human_readable(balance_of, decimals_token),
+        symbol_token,
+        to_human_readable(eth_balance, 18)
+    );
+
+    let desired_token = parse_amount(amount_token, decimals_token, balance_of)?;
+    //max and percentages of ETH leave the gas of the liquidity addition in the balance
+    let spendable = if is_relative(amount_eth) {
+        let estimate = router
+            .add_liquidity_eth(
+                token,
+                desired_token,
+                U256::zero(),
+                U256::zero(),
+                account,
+                deadline,
+            )
+            .value(eth_balance)
+            .tx;
+        let reserve = gas::reserve_or(&client_signed, &estimate, ADD_LIQUIDITY_ETH_GAS).await?;
+        print_reserve(&reserve);
+        eth_balance.saturating_sub(reserve.amount)
+    } else {
+        eth_balance
+    };
+    let desired_eth = parse_amount(amount_eth, 18, spendable)?;
+
+    check_balance(balance_of, desired_token)?;
+    check_balance(spendable, desired_eth)?;
+
+    let (amount_token_in, amount_eth_in) = optimal_amounts(
+        client_signed.clone(),
+        dex,
+        token,
+        weth,
+        desired_token,
+        desired_eth,
+    )
+    .await?;
+
+    let amount_token_min = slippage.min_out(amount_token_in);
+    let amount_eth_min = slippage.min_out(amount_eth_in);
+
+    output::field("slippageBps", slippage.bps());
+    output::field(
+        "token",
+        json!({
+            "address": token,
+            "symbol": symbol_token,
+            "amount": output::amount(amount_token_in, decimals_token),
+            "min": output::amount(amount_token_min, decimals_token),
+        }),
+    );
+    output::field(
+        "eth",
+        json!({
+            "amount": output::amount(amount_eth_in, 18),
+            "min": output::amount(amount_eth_min, 18),
+        }),
+    );
+    text!("Slippage: {}", slippage);
+    text!(
+        "Add {} {} and {} ETH (min {} {} / {} ETH)...",
+        to_human_readable(amount_token_in, decimals_token),
+        symbol_token,
+        to_human_readable(amount_eth_in, 18),
+        to_human_readable(amount_token_min, decimals_token),
+        symbol_token,
+        to_human_readable(amount_eth_min, 18)
+    );
+
+    //check allowance and approve if necessary
+    approve_if_needed(
+        &token_contract,
+        account,
+        router_addr,
+        amount_token_in,
+        decimals_token,
+        &symbol_token,
+    )
+    .await?;
+
+    let receipt_add = send_call(
+        &client_signed,
+        router
+            .add_liquidity_eth(
+                token,
+                amount_token_in,
+                amount_token_min,
+                amount_eth_min,
+                account,
+                deadline,
+            )
+            .value(amount_eth_in),
+        "Adding liquidity...",
+        &[
+            Delta::spend(&symbol_token, decimals_token, amount_token_in),
+            Delta::spend("ETH", 18, amount_eth_in),
+        ],
+    )
+    .await?;
+
+    let pair_addr = get_pair(client_signed.clone(), dex, token, weth).await?;
+    print_minted(&receipt_add, pair_addr, account)?;
+
+    Ok(())
+}
+
+#[tokio::main]
+pub async fn remove_liquidity(
+    token_a_a: &String,
+    token_b_a: &String,
+    amount: &str,
+    slippage: &Slippage,
+    eth: &bool,
+    permit: &bool,
+    dex: &str,
+) -> Result<()> {
+    let client_signed = get_client_signed().await?;
+    let account = client_signed.address();
+    print_state(&client_signed).await?;
+
+    text!("{}", "\n===== REMOVE LIQUIDITY =====\n".bold().blue());
+
+    //Check for alias params
+    let token_a = alias::alias_token(token_a_a)?;
+    let token_b = alias::alias_token(token_b_a)?;
+    let (decimals_a, symbol_a, _) = get_token_metadata(token_a_a).await?;
+    let (decimals_b, symbol_b, _) = get_token_metadata(token_b_a).await?;
+
+    //params
+    let dex = network::dex(dex)?;
+    let router_addr = dex.router;
+    let router = IUniswapRouter::new(router_addr, client_signed.clone());
+    let weth = network::get().weth;
+
+    let deadline = deadline::get(&client_signed).await?;
+
+    let pair_addr = get_pair(client_signed.clone(), dex, token_a, token_b).await?;
+    if pair_addr.is_zero() {
+        return Err(Error::Aborted("no pool for this pair".to_string()).into());
+    }
+    let pair = IUniswapPair::new(pair_addr, client_signed.clone());
+    let lp_contract = IERC20::new(pair_addr, client_signed.clone());
+
+    let balance_lp = at_block(pair.balance_of(account)).call().await?;
+    text!(
+        "{} {} LP ({})",
+        "Actual Balance:".bold(),
+        to_human_readable(balance_lp, 18),
+        balance_lp
+    );
+
+    let liquidity = parse_amount(amount, 18, balance_lp)?;
+
+    check_balance(balance_lp, liquidity)?;
+
+    //underlying tokens owed for this share of the pool
+    let (reserve_a, reserve_b) = get_reserves(&pair, token_a).await?;
+    let total_supply = at_block(pair.total_supply()).call().await?;
+    let expected_a = liquidity * reserve_a / total_supply;
+    let expected_b = liquidity * reserve_b / total_supply;
+
+    let amount_a_min = slippage.min_out(expected_a);
+    let amount_b_min = slippage.min_out(expected_b);
+
+    output::field("slippageBps", slippage.bps());
+    output::field("pair", pair_addr);
+    output::field("liquidity", output::amount(liquidity, 18));
+    output::field(
+        "tokenA",
+        json!({
+            "address": token_a,
+            "symbol": symbol_a,
+            "expected": output::amount(expected_a, decimals_a),
+            "min": output::amount(amount_a_min, decimals_a),
+        }),
+    );
+    output::field(
+        "tokenB",
+        json!({
+            "address": token_b,
+            "symbol": symbol_b,
+            "expected": output::amount(expected_b, decimals_b),
+            "min": output::amount(amount_b_min, decimals_b),
+        }),
+    );
+    text!("Slippage: {}", slippage);
+    text!(
+        "Remove {} LP for {} {} and {} {} (min {} {} / {} {})...",
+        to_human_readable(liquidity, 18),
+        to_human_readable(expected_a, decimals_a),
+        symbol_a,
+        to_human_readable(expected_b, decimals_b),
+        symbol_b,
+        to_human_readable(amount_a_min, decimals_a),
+        symbol_a,
+        to_human_readable(amount_b_min, decimals_b),
+        symbol_b
+    );
+
+    //removeLiquidityETH takes the non-WETH side and unwraps the WETH side
+    let eth_side = if !*eth {
+        None
+    } else if token_b == weth {
+        Some((token_a, amount_a_min, amount_b_min))
+    } else if token_a == weth {
+        Some((token_b, amount_b_min, amount_a_min))
+    } else {
+        return Err(Error::Aborted("--eth needs one of the tokens to be WETH".to_string()).into());
+    };
+
+    //a signed permit replaces the approve transaction of the LP token
+    let permit = if *permit {
+        let signature = sign_permit(
+            client_signed.signer(),
+            &pair,
+            router_addr,
+            liquidity,
+            deadline,
+        )
+        .await?;
+        text!("Permit signed for {} LP", to_human_readable(liquidity, 18));
+        Some(signature)
+    } else {
+        approve_if_needed(&lp_contract, account, router_addr, liquidity, 18, "LP").await?;
+        None
+    };
+
+    let deltas = [
+        Delta::spend("LP", 18, liquidity),
+        Delta::gain(&symbol_a, decimals_a, amount_a_min),
+        Delta::gain(&symbol_b, decimals_b, amount_b_min),
+    ];
+    let receipt_remove = match (eth_side, permit) {
+        (Some((token, amount_token_min, amount_eth_min)), Some((v, r, s))) => {
+            send_call(
+                &client_signed,
+                router.remove_liquidity_eth_with_permit(
+                    token,
+                    liquidity,
+                    amount_token_min,
+                    amount_eth_min,
+                    account,
+                    deadline,
+                    false,
+                    v,
+                    r,
+                    s,
+                ),
+                "Removing liquidity...",
+                &deltas,
+            )
+            .await?
+        }
+        (Some((token, amount_token_min, amount_eth_min)), None) => {
+            send_call(
+                &client_signed,
+                router.remove_liquidity_eth(
+                    token,
+                    liquidity,
+                    amount_token_min,
+                    amount_eth_min,
+                    account,
+                    deadline,
+                ),
+                "Removing liquidity...",
+                &deltas,
+            )
+            .await?
+        }
+        (None, Some((v, r, s))) => {
+            send_call(
+                &client_signed,
+                router.remove_liquidity_with_permit(
+                    token_a,
+                    token_b,
+                    liquidity,
+                    amount_a_min,
+                    amount_b_min,
+                    account,
+                    deadline,
+                    false,
+                    v,
+                    r,
+                    s,
+                ),
+                "Removing liquidity...",
+                &deltas,
+            )
+            .await?
+        }
+        (None, None) => {
+            send_call(
+                &client_signed,
+                router.remove_liquidity(
+                    token_a,
+                    token_b,
+                    liquidity,
+                    amount_a_min,
+                    amount_b_min,
+                    account,
+                    deadline,
+                ),
+                "Removing liquidity...",
+                &deltas,
+            )
+            .await?
+        }
+    };
+
+    print_burned(
+        &receipt_remove,
+        &pair,
+        token_a,
+        (decimals_a, &symbol_a),
+        (decimals_b, &symbol_b),
+    )
+    .await?;
+
+    Ok(())
+}
+
+// EIP-2612 permit letting the spender pull value LP tokens, returned as (v, r, s)
+async fn sign_permit<M: Middleware + 'static>(
+    wallet: &Wallet<SigningKey>,
+    pair: &IUniswapPair<M>,
+    spender: Address,
+    value: U256,
+    deadline: U256,
+) -> Result<(u8, [u8; 32], [u8; 32])> {
+    let owner = wallet.address();
+    let domain_separator = pair.domain_separator().call().await?;
+    let permit_typehash = pair.permit_typehash().call().await?;
+    let nonce = at_block(pair.nonces(owner)).call().await?;
+
+    let struct_hash = keccak256(encode(&[
+        Token::FixedBytes(permit_typehash.to_vec()),
+        Token::Address(owner),
+        Token::Address(spender),
+        Token::Uint(value),
+        Token::Uint(nonce),
+        Token::Uint(deadline),
+    ]));
+    let digest = keccak256([&[0x19, 0x01][..], &domain_separator, &struct_hash].concat());
+    let signature = wallet.sign_hash(H256::from(digest));
+
+    let mut r = [0u8; 32];
+    let mut s = [0u8; 32];
+    signature.r.to_big_endian(&mut r);
+    signature.s.to_big_endian(&mut s);
+    Ok((signature.v as u8, r, s))
+}
+
+// LP tokens minted to the account, read from the pair Transfer event
+fn print_minted(
+    receipt: &Option<ethers::types::TransactionReceipt>,
+    pair_addr: Address,
+    account: Address,
+) -> Result<()> {
+    if let Some(receipt) = receipt {
+        let minted = decode_logs::<TransferFilter>(&receipt.logs, pair_addr)
+            .into_iter()
+            .filter(|t| t.from.is_zero() && t.to == account)
+            .fold(U256::zero(), |acc, t| acc + t.value);
+        output::field("pair", pair_addr);
+        output::field("minted", output::amount(minted, 18));
+        text!(
+            "Minted {} LP ({}) from pair {:?}",
+            to_human_readable(minted, 18),
+            minted,
+            pair_addr
+        );
+    }
+    print_receipt("Add Liquidity", receipt)
+}
+
+// Underlying tokens sent back by the pair, read from its Burn event
+pub async fn print_burned<M: Middleware + 'static>(
+    receipt: &Option<ethers::types::TransactionReceipt>,
+    pair: &IUniswapPair<M>,
+    token_a: Address,
+    (decimals_a, symbol_a): (u8, &str),
+    (decimals_b, symbol_b): (u8, &str),
+) -> Result<()> {
+    if let Some(receipt) = receipt {
+        let token_0 = pair.token_0().call().await?;
+        for burn in decode_logs::<BurnFilter>(&receipt.logs, pair.address()) {
+            let (amount_a, amount_b) = if token_0 == token_a {
+                (burn.amount_0, burn.amount_1)
+            } else {
+                (burn.amount_1, burn.amount_0)
+            };
+            output::field(
+                "received",
+                json!({
+                    "amountA": output::amount(amount_a, decimals_a),
+                    "amountB": output::amount(amount_b, decimals_b),
+                }),
+            );
+            text!(
+                "Received {} {} and {} {}",
+                to_human_readable(amount_a, decimals_a),
+                symbol_a,
+                to_human_readable(amount_b, decimals_b),
+                symbol_b
+            );
+        }
+    }
+    print_receipt("Remove Liquidity", receipt)
+}
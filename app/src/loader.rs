@@ -1,32 +1,64 @@
 use std::io::Write;
 use std::sync::atomic::{AtomicBool, Ordering};
-use std::sync::Arc;
+use std::sync::{Arc, Mutex};
 use std::thread;
 use tokio::time::Duration;
 
+use crate::output;
+
+// Spinner running in its own thread, its message can change while it spins
+pub struct Loader {
+    running: Arc<AtomicBool>,
+    message: Arc<Mutex<String>>,
+}
+
 // Loader functions
-pub fn start_loader(message: &str) -> Arc<AtomicBool> {
-    let running = Arc::new(AtomicBool::new(true));
-    let running_clone = Arc::clone(&running);
-    let msg = message.to_string();
+pub fn start_loader(message: &str) -> Loader {
+    let loader = Loader {
+        running: Arc::new(AtomicBool::new(true)),
+        message: Arc::new(Mutex::new(message.to_string())),
+    };
+    //no spinner in machine readable output
+    if !output::is_text() {
+        loader.running.store(false, Ordering::SeqCst);
+        return loader;
+    }
 
+    let running = Arc::clone(&loader.running);
+    let message = Arc::clone(&loader.message);
     thread::spawn(move || {
         let mut counter = 0;
-        while running_clone.load(Ordering::SeqCst) {
+        let mut width = 0;
+        while running.load(Ordering::SeqCst) {
             let animation = ["|", "/", "-", "\\"];
-            print!("\r{} {}", animation[counter % 4], msg);
+            let msg = message.lock().unwrap_or_else(|e| e.into_inner()).clone();
+            //pad over the rest of a longer previous message
+            width = width.max(msg.len());
+            print!(
+                "\r{} {:<width$}",
+                animation[counter % 4],
+                msg,
+                width = width
+            );
             std::io::stdout().flush().unwrap();
             counter += 1;
             thread::sleep(Duration::from_millis(200));
         }
-        print!("\r{}{}", " ".repeat(msg.len() + 5), "\r");
+        print!("\r{}\r", " ".repeat(width + 5));
         std::io::stdout().flush().unwrap();
     });
 
-    running
+    loader
+}
+
+pub fn set_loader_message(loader: &Loader, message: &str) {
+    *loader.message.lock().unwrap_or_else(|e| e.into_inner()) = message.to_string();
 }
 
-pub fn stop_loader(loader: Arc<AtomicBool>) {
-    loader.store(false, Ordering::SeqCst);
+pub fn stop_loader(loader: Loader) {
+    if !output::is_text() {
+        return;
+    }
+    loader.running.store(false, Ordering::SeqCst);
     thread::sleep(Duration::from_millis(300));
 }
@@ -1,17 +1,74 @@
 mod alias;
+mod amm;
+mod amount;
 mod args;
 mod client;
+mod deadline;
+mod error;
+mod fee;
+mod gas;
+mod liquidity;
 mod loader;
+mod multicall;
+mod network;
+mod output;
 mod read;
+mod router;
+mod sender;
+mod settings;
+mod slippage;
+mod tax;
+mod twap;
 mod tx;
 mod utils;
-use clap::{Parser, Subcommand};
+mod v3;
+mod weth;
+use clap::{error::ErrorKind, Parser, Subcommand, ValueEnum};
 
 #[derive(Parser, Debug)]
 #[clap(about, version, author)]
 struct Value {
     #[clap(subcommand)]
     command: Commands,
+    /// Simulate transactions with eth_call and estimateGas instead of sending them
+    #[clap(long = "dry-run", global = true)]
+    dry_run: bool,
+    /// Block number used by --dry-run (latest by default)
+    #[clap(long = "block", global = true, requires = "dry_run")]
+    block: Option<u64>,
+    /// Output format, csv is only available for tabular commands
+    #[clap(long = "output", global = true, value_enum, default_value_t = output::Format::Text)]
+    output: output::Format,
+    /// Network profile from config/networks.json
+    #[clap(long = "network", global = true, default_value = "mainnet")]
+    network: String,
+    /// Refuse swaps whose price impact is above this percentage
+    #[clap(long = "max-impact", global = true, default_value_t = 5.0, value_parser = router::parse_max_impact)]
+    max_impact: f64,
+    /// Deadline of swaps and liquidity transactions after the latest block (90s, 5m, 1h)
+    #[clap(long = "deadline", global = true, default_value = "5m")]
+    deadline: deadline::Duration,
+    /// Safety margin in percent on the gas kept when sending max or a percentage of the ETH balance
+    #[clap(long = "gas-margin", global = true, default_value_t = 20)]
+    gas_margin: u64,
+    /// Fee preset from the recent blocks (eth_feeHistory)
+    #[clap(long = "fee", global = true, value_enum, default_value_t = fee::Speed::Normal)]
+    fee: fee::Speed,
+    /// Max fee per gas in gwei instead of the preset (gas price on chains without EIP-1559)
+    #[clap(long = "max-fee", global = true)]
+    max_fee: Option<fee::Gwei>,
+    /// Priority fee per gas in gwei instead of the preset
+    #[clap(long = "priority-fee", global = true)]
+    priority_fee: Option<fee::Gwei>,
+    /// Highest max fee per gas in gwei, the preset and --max-fee are lowered to it
+    #[clap(long = "fee-cap", global = true)]
+    fee_cap: Option<fee::Gwei>,
+    /// Hold transactions until the base fee of a new block is at or below this value in gwei
+    #[clap(long = "max-base-fee", global = true)]
+    max_base_fee: Option<fee::Gwei>,
+    /// Longest wait for --max-base-fee before giving up (90s, 5m, 1h)
+    #[clap(long = "max-base-fee-timeout", global = true, default_value = "30m")]
+    max_base_fee_timeout: deadline::Duration,
 }
 #[derive(Subcommand, Debug)]
 enum Commands {
@@ -23,79 +80,205 @@ enum Commands {
     Balanceofadd(args::Tokenaddargs),
     Allowance(args::Allowanceargs),
     Wallet,
+    Pool(args::Poolargs),
+    Twap(args::Twapargs),
+    Portfolio(args::Portfolioargs),
+    Quote(args::Quoteargs),
     //TRANSACTION
     Wrap(args::Wrapargs),
     Unwrap(args::Wrapargs),
     Approve(args::Approveargs),
     Swapeth(args::Swapethargs),
     Swaptoken(args::Swaptokens),
+    Swaptoeth(args::Swaptoethargs),
+    Swapethexact(args::Swapethexactargs),
+    Swaptokenexact(args::Swaptokensexact),
     Transfereth(args::Transfereth),
     Transfertoken(args::Transfertoken),
+    Addliquidity(args::Addliquidityargs),
+    Addliquidityeth(args::Addliquidityethargs),
+    Removeliquidity(args::Removeliquidityargs),
+}
+
+// --output of the command line when it cannot be parsed as a whole
+fn requested_output() -> output::Format {
+    let args: Vec<String> = std::env::args().collect();
+    args.iter()
+        .enumerate()
+        .find_map(|(i, arg)| match arg.strip_prefix("--output") {
+            Some("") => args.get(i + 1).map(|v| v.as_str()),
+            Some(value) => value.strip_prefix('='),
+            None => None,
+        })
+        .and_then(|value| output::Format::from_str(value, true).ok())
+        .unwrap_or_default()
+}
+
+impl Commands {
+    // Commands printing a table, the only ones with a csv output
+    fn is_tabular(&self) -> bool {
+        matches!(self, Commands::Portfolio(_) | Commands::Quote(_))
+    }
 }
 
 fn main() {
-    let value = Value::parse();
-    match &value.command {
-        //READ
-        Commands::Balance => {
-            read::balance().ok();
-        }
-        Commands::Balanceadd(args::Wethaddargs { recipient }) => {
-            read::balance_add(recipient).ok();
-        }
-        Commands::Metadata(args::Tokenargs { token }) => {
-            read::metadata(token).ok();
-        }
-        Commands::Balanceof(args::Tokenargs { token }) => {
-            read::balance_of(token).ok();
+    let value = Value::try_parse().unwrap_or_else(|e| match e.kind() {
+        ErrorKind::DisplayHelp | ErrorKind::DisplayVersion => e.exit(),
+        _ => {
+            //the other options are not parsed, the error still follows --output
+            settings::init(settings::Settings {
+                output: requested_output(),
+                ..Default::default()
+            });
+            let message = e.to_string();
+            let message = message.trim().trim_start_matches("error: ");
+            error::exit_with(error::Error::Input(message.to_string()).into())
         }
+    });
+    settings::init(settings::Settings {
+        dry_run: value.dry_run,
+        block: value.block,
+        output: value.output,
+        max_impact: value.max_impact,
+        deadline: value.deadline,
+        gas_margin: value.gas_margin,
+        fee: fee::Strategy {
+            speed: value.fee,
+            max_fee: value.max_fee,
+            priority_fee: value.priority_fee,
+            cap: value.fee_cap,
+        },
+        max_base_fee: value.max_base_fee,
+        max_base_fee_timeout: value.max_base_fee_timeout,
+    });
+    if value.output != output::Format::Text {
+        colored::control::set_override(false);
+    }
+    if value.output == output::Format::Csv && !value.command.is_tabular() {
+        error::exit_with(
+            error::Error::Config(
+                "--output csv is only available for portfolio and quote".to_string(),
+            )
+            .into(),
+        );
+    }
+    if let Err(e) = network::init(&value.network) {
+        error::exit_with(e.into());
+    }
+    let result = match &value.command {
+        //READ
+        Commands::Balance => read::balance(),
+        Commands::Balanceadd(args::Wethaddargs { recipient }) => read::balance_add(recipient),
+        Commands::Metadata(args::Tokenargs { token }) => read::metadata(token),
+        Commands::Balanceof(args::Tokenargs { token }) => read::balance_of(token),
         Commands::Balanceofadd(args::Tokenaddargs { token, recipient }) => {
-            read::balance_of_add(token, recipient).ok();
+            read::balance_of_add(token, recipient)
         }
         Commands::Allowance(args::Allowanceargs { token_in, spender }) => {
-            read::allowance(token_in, spender).ok();
+            read::allowance(token_in, spender)
         }
-        Commands::Wallet => {
-            read::wallet().ok();
+        Commands::Wallet => read::wallet(),
+        Commands::Pool(args::Poolargs { token_a, token_b }) => read::pool(token_a, token_b),
+        Commands::Portfolio(args::Portfolioargs { recipient, tokens }) => {
+            read::portfolio(recipient, tokens)
         }
+        Commands::Quote(args::Quoteargs {
+            token_in,
+            token_out,
+            amount,
+        }) => read::quote(token_in, token_out, amount),
+        Commands::Twap(args::Twapargs {
+            token_a,
+            token_b,
+            window,
+        }) => twap::twap(token_a, token_b, window),
         //TRANSACTION
         Commands::Swapeth(args::Swapethargs {
             token_out,
             amount,
             slippage,
-        }) => {
-            tx::swap_eth(token_out, amount, slippage).ok();
-        }
+            dex,
+            protocol,
+        }) => tx::swap_eth(token_out, amount, slippage, dex, protocol),
         Commands::Approve(args::Approveargs {
             token_in,
             spender,
             amount,
-        }) => {
-            tx::approve(token_in, spender, amount).ok();
-        }
+        }) => tx::approve(token_in, spender, amount),
         Commands::Swaptoken(args::Swaptokens {
             token_in,
             token_out,
             amount,
             slippage,
-        }) => {
-            tx::swap_tokens(token_in, token_out, amount, slippage).ok();
-        }
-        Commands::Wrap(args::Wrapargs { amount }) => {
-            tx::wrap(amount).ok();
-        }
-        Commands::Unwrap(args::Wrapargs { amount }) => {
-            tx::unwrap(amount).ok();
-        }
+            twap_guard,
+            twap_window,
+            dex,
+            protocol,
+        }) => tx::swap_tokens(
+            token_in,
+            token_out,
+            amount,
+            slippage,
+            twap_guard,
+            twap_window,
+            dex,
+            protocol,
+        ),
+        Commands::Swaptoeth(args::Swaptoethargs {
+            token_in,
+            amount,
+            slippage,
+            dex,
+        }) => tx::swap_to_eth(token_in, amount, slippage, dex),
+        Commands::Swapethexact(args::Swapethexactargs {
+            token_out,
+            amount_out,
+            slippage,
+            dex,
+        }) => tx::swap_eth_exact(token_out, amount_out, slippage, dex),
+        Commands::Swaptokenexact(args::Swaptokensexact {
+            token_in,
+            token_out,
+            amount_out,
+            slippage,
+            dex,
+        }) => tx::swap_tokens_exact(token_in, token_out, amount_out, slippage, dex),
+        Commands::Wrap(args::Wrapargs { amount }) => weth::wrap(amount),
+        Commands::Unwrap(args::Wrapargs { amount }) => weth::unwrap(amount),
         Commands::Transfereth(args::Transfereth { amount, recipient }) => {
-            tx::transfer_eth(amount, recipient).ok();
+            tx::transfer_eth(amount, recipient)
         }
         Commands::Transfertoken(args::Transfertoken {
             token,
             amount,
             recipient,
-        }) => {
-            tx::transfer_token(token, amount, recipient).ok();
-        }
+        }) => tx::transfer_token(token, amount, recipient),
+        Commands::Addliquidity(args::Addliquidityargs {
+            token_a,
+            token_b,
+            amount_a,
+            amount_b,
+            slippage,
+            dex,
+        }) => liquidity::add_liquidity(token_a, token_b, amount_a, amount_b, slippage, dex),
+        Commands::Addliquidityeth(args::Addliquidityethargs {
+            token,
+            amount,
+            amount_eth,
+            slippage,
+            dex,
+        }) => liquidity::add_liquidity_eth(token, amount, amount_eth, slippage, dex),
+        Commands::Removeliquidity(args::Removeliquidityargs {
+            token_a,
+            token_b,
+            liquidity,
+            slippage,
+            eth,
+            permit,
+            dex,
+        }) => liquidity::remove_liquidity(token_a, token_b, liquidity, slippage, eth, permit, dex),
+    };
+    if let Err(report) = result.and_then(|_| output::flush()) {
+        error::exit_with(report);
     }
 }
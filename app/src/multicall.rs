@@ -0,0 +1,81 @@
+use ethers::{
+    abi::Detokenize,
+    contract::{abigen, builders::ContractCall, decode_function_data},
+    core::types::{transaction::eip2718::TypedTransaction, Address, Bytes},
+    providers::Middleware,
+};
+use eyre::{eyre, Result};
+use std::env;
+use std::sync::Arc;
+abigen!(IMulticall, "./abi/multicall.json");
+
+use crate::{network, settings, utils::at_block};
+
+// MULTICALL from .env first, then the multicall of the network profile
+fn multicall_address() -> Result<Option<Address>> {
+    dotenv::dotenv().ok();
+    match env::var("MULTICALL") {
+        Ok(address) if !address.is_empty() => Ok(Some(address.parse::<Address>()?)),
+        _ => Ok(network::get().multicall),
+    }
+}
+
+// Batch of read calls sent in one aggregate call, or one by one without a multicall contract
+pub struct Multicall<M> {
+    client: Arc<M>,
+    calls: Vec<(Address, Bytes)>,
+}
+
+impl<M: Middleware + 'static> Multicall<M> {
+    pub fn new(client: Arc<M>) -> Self {
+        Multicall {
+            client,
+            calls: Vec::new(),
+        }
+    }
+
+    pub fn add<D: Detokenize>(&mut self, call: &ContractCall<M, D>) -> &mut Self {
+        let target = call.tx.to_addr().copied().unwrap_or_default();
+        let data = call.calldata().unwrap_or_default();
+        self.calls.push((target, data));
+        self
+    }
+
+    // Raw return data of every call, in the order they were added
+    pub async fn call(&self) -> Result<Vec<Bytes>> {
+        match multicall_address()? {
+            Some(address) => {
+                let multicall = IMulticall::new(address, self.client.clone());
+                let calls = self
+                    .calls
+                    .iter()
+                    .map(|(target, data)| Call {
+                        target: *target,
+                        call_data: data.clone(),
+                    })
+                    .collect();
+                let (_, return_data) = at_block(multicall.aggregate(calls)).call().await?;
+                Ok(return_data)
+            }
+            None => {
+                let mut return_data = Vec::new();
+                for (target, data) in &self.calls {
+                    let mut tx = TypedTransaction::default();
+                    tx.set_to(*target).set_data(data.clone());
+                    let result = self
+                        .client
+                        .call(&tx, settings::block_id())
+                        .await
+                        .map_err(|e| eyre!("{}", e))?;
+                    return_data.push(result);
+                }
+                Ok(return_data)
+            }
+        }
+    }
+}
+
+// Decode the return data of a call added to a Multicall
+pub fn decode<M, D: Detokenize>(call: &ContractCall<M, D>, data: &Bytes) -> Result<D> {
+    Ok(decode_function_data(&call.function, data, false)?)
+}
@@ -0,0 +1,138 @@
+use ethers::core::types::{Address, H256};
+use serde::Deserialize;
+use std::collections::{BTreeMap, HashMap};
+use std::fs::File;
+use std::io::BufReader;
+use std::sync::OnceLock;
+
+use crate::error::Error;
+
+const NETWORKS_FILE: &str = "config/networks.json";
+
+// Dex used when --dex is not given, and by the read commands
+pub const DEFAULT_DEX: &str = "uniswap";
+
+// UniswapV2 fork: same router and pair ABI, its own contracts, pair bytecode and swap fee
+#[derive(Debug, Clone, Deserialize)]
+pub struct Dex {
+    #[serde(skip)]
+    pub name: String,
+    pub router: Address,
+    pub factory: Address,
+    // Hash of the pair creation code, pair addresses are derived with CREATE2 when known
+    pub init_code_hash: Option<H256>,
+    pub fee_bps: u32,
+}
+
+// Uniswap V3 periphery: QuoterV2 for quotes and SwapRouter for swaps
+#[derive(Debug, Clone, Deserialize)]
+pub struct V3 {
+    pub router: Address,
+    pub quoter: Address,
+}
+
+// Chain profile from config/networks.json, an empty rpc falls back to RPC from .env
+#[derive(Debug, Clone, Deserialize)]
+pub struct Network {
+    #[serde(skip)]
+    pub name: String,
+    pub rpc: String,
+    pub chain_id: u64,
+    pub weth: Address,
+    pub multicall: Option<Address>,
+    // Token aliases of the chain, "weth" is added from the weth address
+    pub tokens: BTreeMap<String, Address>,
+    // Aliases of the intermediate tokens tried by the routing, besides WETH
+    pub base_tokens: Vec<String>,
+    pub dexes: BTreeMap<String, Dex>,
+    pub v3: Option<V3>,
+}
+
+static NETWORK: OnceLock<Network> = OnceLock::new();
+
+// Load the profile selected with --network, once at startup
+pub fn init(name: &str) -> Result<(), Error> {
+    let file = File::open(NETWORKS_FILE)
+        .map_err(|e| Error::Config(format!("{}: {}", NETWORKS_FILE, e)))?;
+    let networks: HashMap<String, Network> = serde_json::from_reader(BufReader::new(file))
+        .map_err(|e| Error::Config(format!("{}: {}", NETWORKS_FILE, e)))?;
+
+    let mut network = networks.get(name).cloned().ok_or_else(|| {
+        let mut names: Vec<&String> = networks.keys().collect();
+        names.sort();
+        Error::Config(format!(
+            "unknown network {} (available: {})",
+            name,
+            names
+                .iter()
+                .map(|n| n.as_str())
+                .collect::<Vec<_>>()
+                .join(", ")
+        ))
+    })?;
+    network.name = name.to_string();
+    network
+        .tokens
+        .entry("weth".to_string())
+        .or_insert(network.weth);
+    if let Some(unknown) = network
+        .base_tokens
+        .iter()
+        .find(|token| !network.tokens.contains_key(*token))
+    {
+        return Err(Error::Config(format!(
+            "{}: base token {} of {} is not in its tokens",
+            NETWORKS_FILE, unknown, name
+        )));
+    }
+    for (dex_name, dex) in network.dexes.iter_mut() {
+        dex.name = dex_name.clone();
+        //the swap fee is taken out of 10000 bps of the input
+        if dex.fee_bps >= 10000 {
+            return Err(Error::Config(format!(
+                "{}: fee_bps {} of dex {} on {} must be below 10000",
+                NETWORKS_FILE, dex.fee_bps, dex_name, name
+            )));
+        }
+    }
+    NETWORK.set(network).ok();
+    Ok(())
+}
+
+pub fn get() -> &'static Network {
+    NETWORK
+        .get()
+        .expect("network profile is loaded in main before any command")
+}
+
+// Dex of the network profile selected with --dex
+pub fn dex(name: &str) -> Result<&'static Dex, Error> {
+    let network = get();
+    network.dexes.get(name).ok_or_else(|| {
+        Error::Config(format!(
+            "unknown dex {} on {} (available: {})",
+            name,
+            network.name,
+            network
+                .dexes
+                .keys()
+                .map(|n| n.as_str())
+                .collect::<Vec<_>>()
+                .join(", ")
+        ))
+    })
+}
+
+// Uniswap V3 contracts of the network, not every profile has them
+pub fn v3() -> Result<&'static V3, Error> {
+    let network = get();
+    network
+        .v3
+        .as_ref()
+        .ok_or_else(|| Error::Config(format!("no Uniswap V3 contracts on {}", network.name)))
+}
+
+// Address of a token alias of the network, None when the chain has no such token
+pub fn token(alias: &str) -> Option<Address> {
+    get().tokens.get(alias).copied()
+}
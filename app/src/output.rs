@@ -0,0 +1,159 @@
+use clap::ValueEnum;
+use ethers::core::types::{TransactionReceipt, U256};
+use eyre::Result;
+use serde::Serialize;
+use serde_json::{json, Map, Value};
+use std::sync::Mutex;
+
+use crate::{error::Error, settings, utils::to_human_readable};
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, ValueEnum)]
+pub enum Format {
+    #[default]
+    Text,
+    Json,
+    Csv,
+}
+
+// Document built while a command runs, printed once at the end with --output json|csv
+#[derive(Default)]
+struct Document {
+    fields: Map<String, Value>,
+    rows: Vec<Vec<(String, String)>>,
+}
+
+static DOCUMENT: Mutex<Option<Document>> = Mutex::new(None);
+
+// println! only for the text output, machine readable outputs print the document instead
+macro_rules! text {
+    ($($arg:tt)*) => {
+        if $crate::output::is_text() {
+            println!($($arg)*);
+        }
+    };
+}
+pub(crate) use text;
+
+pub fn is_text() -> bool {
+    settings::get().output == Format::Text
+}
+
+fn with_document(f: impl FnOnce(&mut Document)) {
+    if is_text() {
+        return;
+    }
+    let mut document = DOCUMENT.lock().unwrap_or_else(|e| e.into_inner());
+    f(document.get_or_insert_with(Document::default));
+}
+
+// Set a top-level field of the document
+pub fn field(key: &str, value: impl Serialize) {
+    let value = serde_json::to_value(value).unwrap_or(Value::Null);
+    with_document(|document| {
+        document.fields.insert(key.to_string(), value);
+    });
+}
+
+// Append to a top-level array of the document
+pub fn push(key: &str, value: impl Serialize) {
+    let value = serde_json::to_value(value).unwrap_or(Value::Null);
+    with_document(|document| {
+        match document
+            .fields
+            .entry(key.to_string())
+            .or_insert_with(|| Value::Array(Vec::new()))
+        {
+            Value::Array(values) => values.push(value),
+            other => *other = Value::Array(vec![value]),
+        }
+    });
+}
+
+// Row of a tabular command, the columns of the first row give the CSV header
+pub fn row(columns: &[(&str, String)]) {
+    let columns = columns
+        .iter()
+        .map(|(name, value)| (name.to_string(), value.clone()))
+        .collect();
+    with_document(|document| document.rows.push(columns));
+}
+
+// Raw and formatted value of an amount
+pub fn amount(raw: U256, decimals: u8) -> Value {
+    json!({
+        "raw": raw.to_string(),
+        "formatted": to_human_readable(raw, decimals),
+        "decimals": decimals,
+    })
+}
+
+pub fn receipt(label: &str, receipt: &TransactionReceipt) -> Value {
+    json!({
+        "label": label,
+        "hash": receipt.transaction_hash,
+        "block": receipt.block_number.map(|b| b.as_u64()),
+        "gasUsed": receipt.gas_used.map(|g| g.to_string()),
+        "effectiveGasPrice": receipt.effective_gas_price.map(|p| p.to_string()),
+        "status": receipt.status.map(|s| s.as_u64()),
+    })
+}
+
+// Print the document of the command in the selected format
+pub fn flush() -> Result<()> {
+    let document = DOCUMENT
+        .lock()
+        .unwrap_or_else(|e| e.into_inner())
+        .take()
+        .unwrap_or_default();
+    match settings::get().output {
+        Format::Text => {}
+        Format::Json => {
+            let mut fields = document.fields;
+            if !document.rows.is_empty() {
+                let rows: Vec<Value> = document
+                    .rows
+                    .into_iter()
+                    .map(|row| Value::Object(row.into_iter().map(|(k, v)| (k, v.into())).collect()))
+                    .collect();
+                fields.insert("rows".to_string(), Value::Array(rows));
+            }
+            println!("{}", serde_json::to_string_pretty(&Value::Object(fields))?);
+        }
+        Format::Csv => {
+            if let Some(first) = document.rows.first() {
+                let header: Vec<&str> = first.iter().map(|(name, _)| name.as_str()).collect();
+                println!("{}", header.join(","));
+            }
+            for row in &document.rows {
+                let values: Vec<String> = row.iter().map(|(_, value)| csv_escape(value)).collect();
+                println!("{}", values.join(","));
+            }
+        }
+    }
+    Ok(())
+}
+
+// With --output json the error is part of the document, the other outputs print it on stderr
+pub fn print_error(error: &Error, causes: &[String]) -> bool {
+    if settings::get().output != Format::Json {
+        return false;
+    }
+    field(
+        "error",
+        json!({
+            "kind": error.kind(),
+            "message": error.to_string(),
+            "causes": causes,
+            "code": error.exit_code(),
+        }),
+    );
+    flush().is_ok()
+}
+
+fn csv_escape(value: &str) -> String {
+    if value.contains([',', '"', '\n']) {
+        format!("\"{}\"", value.replace('"', "\"\""))
+    } else {
+        value.to_string()
+    }
+}
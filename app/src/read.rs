@@ -1,58 +1,98 @@
+use crate::alias;
 use crate::alias::alias_address;
 use crate::alias::alias_token;
-use crate::client::get_client_signed;
-use crate::utils::get_token_metadata;
-use crate::utils::to_human_readable;
+use crate::amount::parse_exact;
+use crate::client::{get_client, get_client_signed};
+use crate::error::Error;
+use crate::multicall::{decode, Multicall};
+use crate::network;
+use crate::output::{self, text};
+use crate::router::{best_route, get_pair, IUniswapFactory};
+use crate::settings;
+use crate::utils::{format_units, pow10, to_human_readable};
+use crate::utils::{get_token_info, get_token_metadata};
 use colored::Colorize;
-use ethers::{contract::abigen, providers::Middleware};
+use ethers::{
+    contract::abigen,
+    core::types::{Address, U256},
+    providers::Middleware,
+};
 use eyre::Result;
-#[path = "alias.rs"]
-mod alias;
+use serde_json::json;
+use std::collections::HashMap;
 
 abigen!(IERC20, "./abi/erc20_abi.json");
+abigen!(IUniswapPool, "./abi/pool_abi.json");
+
+// Fixed point used for prices and shares, and the digits shown for them
+const PRICE_DECIMALS: u8 = 18;
+const SIGNIFICANT_DIGITS: usize = 8;
+
+// Price of one base token in quote tokens, exact from the reserves
+fn price(
+    reserve_quote: U256,
+    decimals_quote: u8,
+    reserve_base: U256,
+    decimals_base: u8,
+) -> Result<String> {
+    let scale = decimals_base
+        .checked_add(PRICE_DECIMALS)
+        .ok_or_else(|| Error::Input(format!("{} decimals do not fit in a U256", decimals_base)))?;
+    let numerator = reserve_quote.checked_mul(pow10(scale)?);
+    let denominator = reserve_base.checked_mul(pow10(decimals_quote)?);
+    let (Some(numerator), Some(denominator)) = (numerator, denominator) else {
+        return Err(Error::Input("Price of the pool does not fit in a U256".to_string()).into());
+    };
+    Ok(format_units(
+        numerator / denominator,
+        PRICE_DECIMALS,
+        Some(SIGNIFICANT_DIGITS),
+    ))
+}
 
 #[tokio::main]
 pub async fn balance_of(token_add: &String) -> Result<()> {
-    println!("{}", "\n===== BALANCE OF TOKEN =====\n".bold().blue());
+    text!("{}", "\n===== BALANCE OF TOKEN =====\n".bold().blue());
     //client
     let client_signed = get_client_signed().await?;
     let account = client_signed.address();
 
-    //Check for alias params
-    let token = alias::alias_token(token_add).unwrap();
-    let token_contract = IERC20::new(token, client_signed);
-    let balance = token_contract.balance_of(account).call().await?;
-    let (decimals_token, symbol_token, _) = get_token_metadata(&token_add).await?;
-    println!(
+    //balance and metadata in one multicall
+    let info = get_token_info(token_add, account, Address::zero()).await?;
+    output::field("token", alias_token(token_add)?);
+    output::field("symbol", &info.symbol);
+    output::field("owner", account);
+    output::field("balance", output::amount(info.balance, info.decimals));
+    text!(
         "Your Balance: \n{} {} ({} wei)",
-        to_human_readable(balance, decimals_token),
-        symbol_token,
-        balance
+        to_human_readable(info.balance, info.decimals),
+        info.symbol,
+        info.balance
     );
 
     Ok(())
 }
 #[tokio::main]
 pub async fn balance_of_add(token_add: &String, recipient_add: &String) -> Result<()> {
-    println!(
+    text!(
         "{}",
         "\n===== BALANCE OF TOKEN FOR ADDRESS=====\n".bold().blue()
     );
-    //client
-    let client_signed = get_client_signed().await?;
     //params
-    let token = alias_token(token_add).unwrap();
-    let recipient = alias_address(recipient_add).unwrap();
+    let recipient = alias_address(recipient_add)?;
 
-    let token_contract = IERC20::new(token, client_signed);
-    let balance = token_contract.balance_of(recipient).call().await?;
-    let (decimals_token, symbol_token, _) = get_token_metadata(&token_add).await?;
-    println!(
+    //balance and metadata in one multicall
+    let info = get_token_info(token_add, recipient, Address::zero()).await?;
+    output::field("token", alias_token(token_add)?);
+    output::field("symbol", &info.symbol);
+    output::field("owner", recipient);
+    output::field("balance", output::amount(info.balance, info.decimals));
+    text!(
         "Balance of {} :\n{} {} ({} wei)",
         recipient,
-        to_human_readable(balance, decimals_token),
-        symbol_token,
-        balance
+        to_human_readable(info.balance, info.decimals),
+        info.symbol,
+        info.balance
     );
 
     Ok(())
@@ -60,13 +100,17 @@ pub async fn balance_of_add(token_add: &String, recipient_add: &String) -> Resul
 
 #[tokio::main]
 pub async fn balance() -> Result<()> {
-    println!("{}", "\n===== BALANCE ETH =====\n".bold().blue());
+    text!("{}", "\n===== BALANCE ETH =====\n".bold().blue());
     //client
     let client_signed = get_client_signed().await?;
     let account = client_signed.address();
 
-    let balance = client_signed.get_balance(account, None).await?;
-    println!(
+    let balance = client_signed
+        .get_balance(account, settings::block_id())
+        .await?;
+    output::field("owner", account);
+    output::field("balance", output::amount(balance, 18));
+    text!(
         "Your ETH Balance :\n{} ETH ({} wei)",
         to_human_readable(balance, 18),
         balance
@@ -76,14 +120,18 @@ pub async fn balance() -> Result<()> {
 }
 #[tokio::main]
 pub async fn balance_add(recipient_add: &String) -> Result<()> {
-    println!("{}", "\n===== BALANCE ETH OF ADDRESS=====\n".bold().blue());
+    text!("{}", "\n===== BALANCE ETH OF ADDRESS=====\n".bold().blue());
     //client
     let client_signed = get_client_signed().await?;
     //params
-    let recipient = alias_address(recipient_add).unwrap();
+    let recipient = alias_address(recipient_add)?;
 
-    let balance = client_signed.get_balance(recipient, None).await?;
-    println!(
+    let balance = client_signed
+        .get_balance(recipient, settings::block_id())
+        .await?;
+    output::field("owner", recipient);
+    output::field("balance", output::amount(balance, 18));
+    text!(
         "ETH Balance of {} :\n{} ETH ({} wei)",
         recipient,
         to_human_readable(balance, 18),
@@ -94,47 +142,65 @@ pub async fn balance_add(recipient_add: &String) -> Result<()> {
 }
 #[tokio::main]
 pub async fn metadata(token_add: &String) -> Result<()> {
-    println!("{}", "\n===== METADATA TOKEN=====\n".bold().blue());
+    text!("{}", "\n===== METADATA TOKEN=====\n".bold().blue());
 
     //Check for alias params
-    let token = alias_token(token_add).unwrap();
-    let (decimals_token, symbol_token, name_token) = get_token_metadata(&token_add).await?;
-    println!("Address: {}", token);
-    println!("Symbol: {}", symbol_token);
-    println!("Name: {}", name_token);
-    println!("Decimals : {}", decimals_token);
+    let token = alias_token(token_add)?;
+    let (decimals_token, symbol_token, name_token) = get_token_metadata(token_add).await?;
+    output::field("address", token);
+    output::field("symbol", &symbol_token);
+    output::field("name", &name_token);
+    output::field("decimals", decimals_token);
+    text!("Address: {}", token);
+    text!("Symbol: {}", symbol_token);
+    text!("Name: {}", name_token);
+    text!("Decimals : {}", decimals_token);
 
     Ok(())
 }
 
 #[tokio::main]
 pub async fn allowance(token_a: &String, spender_a: &String) -> Result<()> {
-    println!("{}", "\n===== ALLOWANCE =====\n".bold().blue());
+    text!("{}", "\n===== ALLOWANCE =====\n".bold().blue());
     //client
     let client_signed = get_client_signed().await?;
     let account = client_signed.address();
 
     //Check for alias params
-    let token = alias::alias_token(token_a).unwrap();
-    let spender = alias::alias_address(spender_a).unwrap();
+    let spender = alias::alias_address(spender_a)?;
 
-    let token_contract = IERC20::new(token, client_signed);
-    let allow = token_contract.allowance(account, spender).call().await?;
-    println!("{} allowed to address {}", allow, spender);
+    //allowance and metadata in one multicall
+    let info = get_token_info(token_a, account, spender).await?;
+    output::field("token", alias_token(token_a)?);
+    output::field("symbol", &info.symbol);
+    output::field("owner", account);
+    output::field("spender", spender);
+    output::field("allowance", output::amount(info.allowance, info.decimals));
+    text!(
+        "{} {} ({}) allowed to address {}",
+        to_human_readable(info.allowance, info.decimals),
+        info.symbol,
+        info.allowance,
+        spender
+    );
 
     Ok(())
 }
 
 #[tokio::main]
 pub async fn wallet() -> Result<()> {
-    println!("{}", "\n===== WALLET =====\n".bold().blue());
+    text!("{}", "\n===== WALLET =====\n".bold().blue());
 
     //client
     let client_signed = get_client_signed().await?;
     let account = client_signed.address();
-    let balance = client_signed.get_balance(account, None).await?;
-    println!("Wallet: {}", account);
-    println!(
+    let balance = client_signed
+        .get_balance(account, settings::block_id())
+        .await?;
+    output::field("wallet", account);
+    output::field("balance", output::amount(balance, 18));
+    text!("Wallet: {}", account);
+    text!(
         "ETH Balance :\n{} ETH ({} wei)",
         to_human_readable(balance, 18),
         balance
@@ -142,3 +208,451 @@ pub async fn wallet() -> Result<()> {
 
     Ok(())
 }
+
+#[tokio::main]
+pub async fn pool(token_a: &String, token_b: &Option<String>) -> Result<()> {
+    text!("{}", "\n===== POOL =====\n".bold().blue());
+    //client
+    let client_signed = get_client_signed().await?;
+    let account = client_signed.address();
+
+    //params: two tokens resolved through the factory, or the pair address itself
+    let pair_addr = match token_b {
+        Some(token_b) => {
+            let token_0 = alias_token(token_a)?;
+            let token_1 = alias_token(token_b)?;
+            let dex = network::dex(network::DEFAULT_DEX)?;
+            get_pair(client_signed.clone(), dex, token_0, token_1).await?
+        }
+        None => alias_address(token_a)?,
+    };
+    if pair_addr.is_zero() {
+        output::field("pair", Option::<Address>::None);
+        text!("{}", "No UniswapV2 pool for this pair.".red());
+        return Ok(());
+    }
+
+    //pool state in one multicall
+    let pool_contract = IUniswapPool::new(pair_addr, client_signed.clone());
+    let token_0 = pool_contract.token_0();
+    let token_1 = pool_contract.token_1();
+    let reserves = pool_contract.get_reserves();
+    let total_supply = pool_contract.total_supply();
+    let balance_lp = pool_contract.balance_of(account);
+    let results = Multicall::new(client_signed)
+        .add(&token_0)
+        .add(&token_1)
+        .add(&reserves)
+        .add(&total_supply)
+        .add(&balance_lp)
+        .call()
+        .await?;
+    let token_0 = decode(&token_0, &results[0])?;
+    let token_1 = decode(&token_1, &results[1])?;
+    let (reserve_0, reserve_1, last_update) = decode(&reserves, &results[2])?;
+    let total_supply = decode(&total_supply, &results[3])?;
+    let balance_lp = decode(&balance_lp, &results[4])?;
+    let (decimals_0, symbol_0, _) = get_token_metadata(&format!("{:?}", token_0)).await?;
+    let (decimals_1, symbol_1, _) = get_token_metadata(&format!("{:?}", token_1)).await?;
+
+    let reserve_0 = U256::from(reserve_0);
+    let reserve_1 = U256::from(reserve_1);
+    output::field("pair", pair_addr);
+    output::field(
+        "token0",
+        json!({"address": token_0, "symbol": symbol_0, "reserve": output::amount(reserve_0, decimals_0)}),
+    );
+    output::field(
+        "token1",
+        json!({"address": token_1, "symbol": symbol_1, "reserve": output::amount(reserve_1, decimals_1)}),
+    );
+    output::field("lastUpdate", last_update);
+    output::field("lpTotalSupply", output::amount(total_supply, 18));
+    output::field("lpBalance", output::amount(balance_lp, 18));
+
+    text!("Pair: {:?}", pair_addr);
+    text!("Token0: {} ({:?})", symbol_0, token_0);
+    text!("Token1: {} ({:?})", symbol_1, token_1);
+    text!(
+        "Reserves: \n{} {} ({} wei)\n{} {} ({} wei)",
+        to_human_readable(reserve_0, decimals_0),
+        symbol_0,
+        reserve_0,
+        to_human_readable(reserve_1, decimals_1),
+        symbol_1,
+        reserve_1
+    );
+    text!("Last update: {}", last_update);
+    if !reserve_0.is_zero() && !reserve_1.is_zero() {
+        let price_0 = price(reserve_1, decimals_1, reserve_0, decimals_0)?;
+        let price_1 = price(reserve_0, decimals_0, reserve_1, decimals_1)?;
+        output::field("price0", &price_0);
+        output::field("price1", &price_1);
+        text!("Price: \n1 {} = {} {}", symbol_0, price_0, symbol_1);
+        text!("1 {} = {} {}", symbol_1, price_1, symbol_0);
+    }
+
+    //wallet share of the pool
+    text!(
+        "LP Total Supply: {} ({} wei)",
+        to_human_readable(total_supply, 18),
+        total_supply
+    );
+    text!(
+        "Your LP Balance: {} ({} wei)",
+        to_human_readable(balance_lp, 18),
+        balance_lp
+    );
+    if !total_supply.is_zero() && !balance_lp.is_zero() {
+        let share_0 = balance_lp * reserve_0 / total_supply;
+        let share_1 = balance_lp * reserve_1 / total_supply;
+        let share = format_units(
+            balance_lp * U256::exp10(PRICE_DECIMALS as usize + 2) / total_supply,
+            PRICE_DECIMALS,
+            Some(SIGNIFICANT_DIGITS),
+        );
+        output::field(
+            "share",
+            json!({
+                "percent": share,
+                "amount0": output::amount(share_0, decimals_0),
+                "amount1": output::amount(share_1, decimals_1),
+            }),
+        );
+        text!(
+            "Your Share: {} %\n{} {}\n{} {}",
+            share,
+            to_human_readable(share_0, decimals_0),
+            symbol_0,
+            to_human_readable(share_1, decimals_1),
+            symbol_1
+        );
+    }
+
+    Ok(())
+}
+
+#[tokio::main]
+pub async fn portfolio(recipient_add: &Option<String>, extra_tokens: &[String]) -> Result<()> {
+    text!("{}", "\n===== PORTFOLIO =====\n".bold().blue());
+    //client
+    let client_signed = get_client_signed().await?;
+    let owner = match recipient_add {
+        Some(recipient_add) => alias_address(recipient_add)?,
+        None => client_signed.address(),
+    };
+
+    //params: every token alias of the network plus the extra ones
+    let weth = network::get().weth;
+    //values in USD need a USDC on the chain
+    let usdc = network::token("usdc");
+    let mut tokens: Vec<Address> = Vec::new();
+    for (_, token) in alias::alias_tokens() {
+        if !tokens.contains(&token) {
+            tokens.push(token);
+        }
+    }
+    for token_add in extra_tokens {
+        let token = alias_token(token_add)?;
+        if !tokens.contains(&token) {
+            tokens.push(token);
+        }
+    }
+
+    //balances and metadata of every token in one multicall
+    let contracts: Vec<IERC20<_>> = tokens
+        .iter()
+        .map(|token| IERC20::new(*token, client_signed.clone()))
+        .collect();
+    let calls: Vec<_> = contracts
+        .iter()
+        .map(|c| (c.decimals(), c.symbol(), c.balance_of(owner)))
+        .collect();
+    let mut multicall = Multicall::new(client_signed.clone());
+    for (decimals, symbol, balance) in &calls {
+        multicall.add(decimals).add(symbol).add(balance);
+    }
+    let results = multicall.call().await?;
+
+    let mut holdings = Vec::new();
+    for (i, (decimals, symbol, balance)) in calls.iter().enumerate() {
+        let balance = decode(balance, &results[3 * i + 2])?;
+        if !balance.is_zero() {
+            holdings.push((
+                tokens[i],
+                decode(decimals, &results[3 * i])?,
+                decode(symbol, &results[3 * i + 1])?,
+                balance,
+            ));
+        }
+    }
+
+    //WETH pools of the held tokens and of USDC, for the valuation
+    let factory = IUniswapFactory::new(
+        network::dex(network::DEFAULT_DEX)?.factory,
+        client_signed.clone(),
+    );
+    let mut priced: Vec<Address> = holdings
+        .iter()
+        .map(|(token, _, _, _)| *token)
+        .filter(|token| *token != weth)
+        .collect();
+    priced.extend(usdc);
+    let pair_calls: Vec<_> = priced
+        .iter()
+        .map(|token| factory.get_pair(*token, weth))
+        .collect();
+    let mut multicall = Multicall::new(client_signed.clone());
+    for call in &pair_calls {
+        multicall.add(call);
+    }
+    let results = multicall.call().await?;
+    let mut pairs = Vec::new();
+    for (i, call) in pair_calls.iter().enumerate() {
+        let pair: Address = decode(call, &results[i])?;
+        if !pair.is_zero() {
+            pairs.push((priced[i], pair));
+        }
+    }
+
+    let pools: Vec<IUniswapPool<_>> = pairs
+        .iter()
+        .map(|(_, pair)| IUniswapPool::new(*pair, client_signed.clone()))
+        .collect();
+    let reserve_calls: Vec<_> = pools
+        .iter()
+        .map(|p| (p.token_0(), p.get_reserves()))
+        .collect();
+    let mut multicall = Multicall::new(client_signed.clone());
+    for (token_0, reserves) in &reserve_calls {
+        multicall.add(token_0).add(reserves);
+    }
+    let results = multicall.call().await?;
+
+    //WETH and token reserves of each pool, values are converted exactly through them
+    let mut reserves: Vec<(Address, U256, U256)> = Vec::new();
+    for (i, (token_0, pool_reserves)) in reserve_calls.iter().enumerate() {
+        let token_0: Address = decode(token_0, &results[2 * i])?;
+        let (reserve_0, reserve_1, _) = decode(pool_reserves, &results[2 * i + 1])?;
+        let (reserve_token, reserve_weth) = if token_0 == weth {
+            (reserve_1, reserve_0)
+        } else {
+            (reserve_0, reserve_1)
+        };
+        if reserve_token > 0 {
+            reserves.push((
+                pairs[i].0,
+                U256::from(reserve_token),
+                U256::from(reserve_weth),
+            ));
+        }
+    }
+    let value_in_eth = |token: Address, amount: U256| -> Option<U256> {
+        if token == weth {
+            return Some(amount);
+        }
+        reserves
+            .iter()
+            .find(|(t, _, _)| *t == token)
+            .map(|(_, reserve_token, reserve_weth)| amount * reserve_weth / reserve_token)
+    };
+    let decimals_usdc = match usdc {
+        Some(usdc) => get_token_metadata(&format!("{:?}", usdc)).await?.0,
+        None => 6,
+    };
+    let value_in_usd = |value_eth: U256| -> Option<U256> {
+        reserves
+            .iter()
+            .find(|(t, _, _)| Some(*t) == usdc)
+            .map(|(_, reserve_usdc, reserve_weth)| value_eth * reserve_usdc / reserve_weth)
+    };
+
+    text!("Wallet: {:?}\n", owner);
+    text!(
+        "{:<10} {:>26} {:>30} {:>14} {:>14}",
+        "Symbol".bold(),
+        "Amount".bold(),
+        "Wei".bold(),
+        "ETH".bold(),
+        "USD".bold()
+    );
+
+    let mut total_eth = U256::zero();
+    let eth_balance = client_signed
+        .get_balance(owner, settings::block_id())
+        .await?;
+    let mut rows = Vec::new();
+    if !eth_balance.is_zero() {
+        rows.push(("ETH".to_string(), eth_balance, 18, Some(eth_balance)));
+    }
+    for (token, decimals, symbol, balance) in holdings {
+        rows.push((symbol, balance, decimals, value_in_eth(token, balance)));
+    }
+    for (symbol, balance, decimals, value_eth) in rows {
+        total_eth += value_eth.unwrap_or_default();
+        let value_usd = value_eth.and_then(value_in_usd);
+        output::row(&[
+            ("symbol", symbol.clone()),
+            ("balance", to_human_readable(balance, decimals)),
+            ("balanceRaw", balance.to_string()),
+            ("decimals", decimals.to_string()),
+            (
+                "valueEth",
+                value_eth.map_or(String::new(), |v| to_human_readable(v, 18)),
+            ),
+            (
+                "valueUsd",
+                value_usd.map_or(String::new(), |v| to_human_readable(v, decimals_usdc)),
+            ),
+        ]);
+        text!(
+            "{:<10} {:>26} {:>30} {:>14} {:>14}",
+            symbol,
+            format_units(balance, decimals, Some(SIGNIFICANT_DIGITS)),
+            balance,
+            value_eth.map_or("-".to_string(), |v| format_units(v, 18, Some(6))),
+            value_usd.map_or("-".to_string(), |v| format_units(v, decimals_usdc, Some(6)))
+        );
+    }
+
+    output::field("wallet", owner);
+    output::field("totalEth", output::amount(total_eth, 18));
+    if let Some(usd) = value_in_usd(total_eth) {
+        output::field("totalUsd", output::amount(usd, decimals_usdc));
+    }
+
+    text!(
+        "\n{} {} ETH{}",
+        "Total:".bold(),
+        format_units(total_eth, 18, Some(6)),
+        value_in_usd(total_eth).map_or(String::new(), |usd| format!(
+            " ({} USD)",
+            format_units(usd, decimals_usdc, Some(6))
+        ))
+    );
+
+    Ok(())
+}
+
+#[tokio::main]
+pub async fn quote(token_in_a: &String, token_out_a: &String, amount: &str) -> Result<()> {
+    text!("{}", "\n===== QUOTE =====\n".bold().blue());
+    //client
+    let client = get_client().await?;
+
+    //params
+    let token_in = alias_token(token_in_a)?;
+    let token_out = alias_token(token_out_a)?;
+    let (decimals_in, symbol_in, _) = get_token_metadata(token_in_a).await?;
+    let (decimals_out, symbol_out, _) = get_token_metadata(token_out_a).await?;
+    let amount_in = parse_exact(amount, decimals_in)?;
+    output::field("tokenIn", json!({"address": token_in, "symbol": symbol_in}));
+    output::field(
+        "tokenOut",
+        json!({"address": token_out, "symbol": symbol_out}),
+    );
+    output::field("amountIn", output::amount(amount_in, decimals_in));
+    text!(
+        "{} {} -> {} on {}\n",
+        to_human_readable(amount_in, decimals_in),
+        symbol_in,
+        symbol_out,
+        network::get().name
+    );
+
+    //best getAmountsOut route on every V2 fork of the network
+    let mut quotes = Vec::new();
+    for dex in network::get().dexes.values() {
+        let route = best_route(client.clone(), dex, token_in, token_out, amount_in).await;
+        quotes.push((dex, route));
+    }
+    let best = quotes
+        .iter()
+        .filter_map(|(_, route)| route.as_ref().ok().map(|r| r.amount_out()))
+        .max();
+
+    let mut symbols: HashMap<Address, String> = HashMap::new();
+    text!(
+        "{:<14} {:>6} {:>30}  {}",
+        "Dex".bold(),
+        "Fee".bold(),
+        "Amount out".bold(),
+        "Path".bold()
+    );
+    for (dex, route) in quotes {
+        let fee = format!("{}%", format_units(U256::from(dex.fee_bps), 2, None));
+        let route = match route {
+            Ok(route) => route,
+            Err(e) => {
+                output::row(&[
+                    ("dex", dex.name.clone()),
+                    ("feeBps", dex.fee_bps.to_string()),
+                    ("amountOut", String::new()),
+                    ("amountOutRaw", String::new()),
+                    ("path", String::new()),
+                    ("best", false.to_string()),
+                    ("error", e.to_string()),
+                ]);
+                text!(
+                    "{:<14} {:>6} {:>30}  {}",
+                    dex.name,
+                    fee,
+                    "-",
+                    e.to_string().red()
+                );
+                continue;
+            }
+        };
+
+        let mut path = Vec::new();
+        for token in &route.path {
+            if !symbols.contains_key(token) {
+                let (_, symbol, _) = get_token_metadata(&format!("{:?}", token)).await?;
+                symbols.insert(*token, symbol);
+            }
+            path.push(symbols[token].clone());
+        }
+        let is_best = Some(route.amount_out()) == best;
+        output::row(&[
+            ("dex", dex.name.clone()),
+            ("feeBps", dex.fee_bps.to_string()),
+            (
+                "amountOut",
+                to_human_readable(route.amount_out(), decimals_out),
+            ),
+            ("amountOutRaw", route.amount_out().to_string()),
+            ("path", path.join(">")),
+            ("best", is_best.to_string()),
+            ("error", String::new()),
+        ]);
+        let amount_out = format_units(route.amount_out(), decimals_out, Some(SIGNIFICANT_DIGITS));
+        if is_best {
+            text!(
+                "{:<14} {:>6} {:>30}  {}",
+                dex.name.green().bold(),
+                fee,
+                amount_out.green().bold(),
+                path.join(" -> ")
+            );
+        } else {
+            text!(
+                "{:<14} {:>6} {:>30}  {}",
+                dex.name,
+                fee,
+                amount_out,
+                path.join(" -> ")
+            );
+        }
+    }
+
+    match best {
+        Some(_) => Ok(()),
+        None => Err(Error::Aborted(format!(
+            "No route found between {} and {} on any dex of {}",
+            symbol_in,
+            symbol_out,
+            network::get().name
+        ))
+        .into()),
+    }
+}
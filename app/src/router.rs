@@ -0,0 +1,529 @@
+use clap::ValueEnum;
+use colored::Colorize;
+use ethers::{
+    contract::abigen,
+    core::types::{Address, U256, U512},
+    providers::Middleware,
+    utils::{get_create2_address_from_hash, keccak256},
+};
+use eyre::{eyre, Result};
+use serde_json::json;
+use std::collections::HashMap;
+use std::sync::Arc;
+abigen!(IUniswapRouter, "./abi/router_univ2.json");
+abigen!(IUniswapFactory, "./abi/factory_univ2.json");
+
+use crate::{
+    amm::{get_amounts_in, get_amounts_out, mid_amount_out, shortfall_bps},
+    error::Error,
+    liquidity::{get_reserves, IUniswapPair},
+    network::{self, Dex},
+    output::{self, text},
+    settings,
+    utils::{at_block, format_units, get_token_metadata, pow10, to_human_readable},
+    v3::{best_route_v3, mid_amount_out_v3, print_route_v3, spot_prices, V3Route},
+};
+
+// Uniswap version used by a swap, best keeps the larger of both quotes
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, ValueEnum)]
+pub enum Protocol {
+    #[default]
+    V2,
+    V3,
+    Best,
+}
+
+#[derive(Debug, Clone)]
+pub struct Route {
+    pub path: Vec<Address>,
+    pub amounts: Vec<U256>,
+}
+
+impl Route {
+    pub fn amount_in(&self) -> U256 {
+        self.amounts[0]
+    }
+    pub fn amount_out(&self) -> U256 {
+        self.amounts[self.amounts.len() - 1]
+    }
+}
+
+// Route picked with --protocol
+#[derive(Debug, Clone)]
+pub enum SwapRoute {
+    V2(Route),
+    V3(V3Route),
+}
+
+impl SwapRoute {
+    pub fn path(&self) -> &[Address] {
+        match self {
+            SwapRoute::V2(route) => &route.path,
+            SwapRoute::V3(route) => &route.path,
+        }
+    }
+    pub fn amount_out(&self) -> U256 {
+        match self {
+            SwapRoute::V2(route) => route.amount_out(),
+            SwapRoute::V3(route) => route.amount_out,
+        }
+    }
+}
+
+// Intermediate tokens tried between token_in and token_out: the WETH of the network and its
+// base_tokens
+pub fn base_tokens() -> Vec<Address> {
+    let network = network::get();
+    let mut tokens = vec![network.weth];
+    tokens.extend(
+        network
+            .base_tokens
+            .iter()
+            .filter_map(|name| network::token(name)),
+    );
+    tokens
+}
+
+// Pair of the dex for two tokens, the zero address when there is no pool
+pub async fn get_pair<M: Middleware + 'static>(
+    client: Arc<M>,
+    dex: &Dex,
+    token_a: Address,
+    token_b: Address,
+) -> Result<Address> {
+    let hash = match dex.init_code_hash {
+        Some(hash) => hash,
+        None => {
+            let factory = IUniswapFactory::new(dex.factory, client);
+            return Ok(at_block(factory.get_pair(token_a, token_b)).call().await?);
+        }
+    };
+
+    //same as UniswapV2Library.pairFor, the pool exists once code is deployed there
+    let (token_0, token_1) = if token_a < token_b {
+        (token_a, token_b)
+    } else {
+        (token_b, token_a)
+    };
+    let salt = keccak256([token_0.as_bytes(), token_1.as_bytes()].concat());
+    let pair = get_create2_address_from_hash(dex.factory, salt, hash.as_bytes().to_vec());
+    let code = client
+        .get_code(pair, settings::block_id())
+        .await
+        .map_err(|e| eyre!("{}", e))?;
+    Ok(if code.is_empty() {
+        Address::zero()
+    } else {
+        pair
+    })
+}
+
+// Every direct, 2-hop and 3-hop path whose pairs all exist on the dex
+pub async fn candidate_paths<M: Middleware + 'static>(
+    client: Arc<M>,
+    dex: &Dex,
+    token_in: Address,
+    token_out: Address,
+) -> Result<Vec<Vec<Address>>> {
+    let bases: Vec<Address> = base_tokens()
+        .into_iter()
+        .filter(|b| *b != token_in && *b != token_out)
+        .collect();
+
+    let mut candidates = vec![vec![token_in, token_out]];
+    for b in &bases {
+        candidates.push(vec![token_in, *b, token_out]);
+    }
+    for b1 in &bases {
+        for b2 in &bases {
+            if b1 != b2 {
+                candidates.push(vec![token_in, *b1, *b2, token_out]);
+            }
+        }
+    }
+
+    //keep only paths where every hop has a pool
+    let mut pairs: HashMap<(Address, Address), bool> = HashMap::new();
+    let mut paths = Vec::new();
+    for path in candidates {
+        let mut exists = true;
+        for hop in path.windows(2) {
+            let key = if hop[0] < hop[1] {
+                (hop[0], hop[1])
+            } else {
+                (hop[1], hop[0])
+            };
+            let found = match pairs.get(&key) {
+                Some(found) => *found,
+                None => {
+                    let pair = get_pair(client.clone(), dex, key.0, key.1).await?;
+                    let found = !pair.is_zero();
+                    pairs.insert(key, found);
+                    found
+                }
+            };
+            if !found {
+                exists = false;
+                break;
+            }
+        }
+        if exists {
+            paths.push(path);
+        }
+    }
+
+    Ok(paths)
+}
+
+// Quote every candidate path with getAmountsOut and keep the one with the largest output
+pub async fn best_route<M: Middleware + 'static>(
+    client: Arc<M>,
+    dex: &Dex,
+    token_in: Address,
+    token_out: Address,
+    amount_in: U256,
+) -> Result<Route> {
+    let router = IUniswapRouter::new(dex.router, client.clone());
+    let paths = candidate_paths(client, dex, token_in, token_out).await?;
+
+    let mut best: Option<Route> = None;
+    for path in paths {
+        //a path can exist but lack liquidity, getAmountsOut reverts in that case
+        let amounts = match at_block(router.get_amounts_out(amount_in, path.clone()))
+            .call()
+            .await
+        {
+            Ok(amounts) => amounts,
+            Err(_) => continue,
+        };
+        let route = Route { path, amounts };
+        if best
+            .as_ref()
+            .is_none_or(|b| route.amount_out() > b.amount_out())
+        {
+            best = Some(route);
+        }
+    }
+
+    best.ok_or_else(|| {
+        Error::Aborted(format!(
+            "No {} route found between {:?} and {:?}",
+            dex.name, token_in, token_out
+        ))
+        .into()
+    })
+}
+
+// Quote every candidate path with getAmountsIn and keep the one needing the smallest input
+pub async fn best_route_exact_out<M: Middleware + 'static>(
+    client: Arc<M>,
+    dex: &Dex,
+    token_in: Address,
+    token_out: Address,
+    amount_out: U256,
+) -> Result<Route> {
+    let router = IUniswapRouter::new(dex.router, client.clone());
+    let paths = candidate_paths(client, dex, token_in, token_out).await?;
+
+    let mut best: Option<Route> = None;
+    for path in paths {
+        let amounts = match at_block(router.get_amounts_in(amount_out, path.clone()))
+            .call()
+            .await
+        {
+            Ok(amounts) => amounts,
+            Err(_) => continue,
+        };
+        let route = Route { path, amounts };
+        if best
+            .as_ref()
+            .is_none_or(|b| route.amount_in() < b.amount_in())
+        {
+            best = Some(route);
+        }
+    }
+
+    best.ok_or_else(|| {
+        Error::Aborted(format!(
+            "No {} route found between {:?} and {:?}",
+            dex.name, token_in, token_out
+        ))
+        .into()
+    })
+}
+
+pub async fn print_route(route: &Route) -> Result<()> {
+    let mut symbols = Vec::new();
+    let mut decimals = Vec::new();
+    for token in &route.path {
+        let (decimals_token, symbol_token, _) = get_token_metadata(&format!("{:?}", token)).await?;
+        symbols.push(symbol_token);
+        decimals.push(decimals_token);
+    }
+
+    output::field(
+        "route",
+        json!({
+            "path": route.path,
+            "symbols": symbols,
+            "amounts": route
+                .amounts
+                .iter()
+                .zip(&decimals)
+                .map(|(amount, decimals)| output::amount(*amount, *decimals))
+                .collect::<Vec<_>>(),
+        }),
+    );
+    text!("{} {}", "Route:".bold(), symbols.join(" -> "));
+    for i in 0..route.path.len() - 1 {
+        text!(
+            "  {} {} -> {} {}",
+            to_human_readable(route.amounts[i], decimals[i]),
+            symbols[i],
+            to_human_readable(route.amounts[i + 1], decimals[i + 1]),
+            symbols[i + 1]
+        );
+    }
+
+    Ok(())
+}
+
+// Quote the swap on the dex (V2), on Uniswap V3, or on both and keep the larger output
+#[allow(clippy::too_many_arguments)]
+pub async fn select_route<M: Middleware + 'static>(
+    client: Arc<M>,
+    protocol: Protocol,
+    dex: &Dex,
+    token_in: Address,
+    token_out: Address,
+    amount_in: U256,
+    decimals_out: u8,
+) -> Result<SwapRoute> {
+    match protocol {
+        Protocol::V2 | Protocol::V3 => {
+            pick_route(client, protocol, dex, token_in, token_out, amount_in).await
+        }
+        Protocol::Best => {
+            let (v2, v3) = quote_both(client, dex, token_in, token_out, amount_in).await;
+            let amount_v2 = v2.as_ref().ok().map(|r| r.amount_out());
+            let amount_v3 = v3.as_ref().ok().map(|r| r.amount_out);
+            let quote = |amount: Option<U256>| -> String {
+                amount.map_or("-".to_string(), |a| to_human_readable(a, decimals_out))
+            };
+            output::field(
+                "quotes",
+                json!({
+                    "v2": amount_v2.map(|a| output::amount(a, decimals_out)),
+                    "v3": amount_v3.map(|a| output::amount(a, decimals_out)),
+                }),
+            );
+            text!(
+                "Quotes: V2 ({}) {} / V3 {}",
+                dex.name,
+                quote(amount_v2),
+                quote(amount_v3)
+            );
+
+            larger(v2, v3)
+        }
+    }
+}
+
+// Route select_route would pick, without printing the quotes
+pub async fn pick_route<M: Middleware + 'static>(
+    client: Arc<M>,
+    protocol: Protocol,
+    dex: &Dex,
+    token_in: Address,
+    token_out: Address,
+    amount_in: U256,
+) -> Result<SwapRoute> {
+    match protocol {
+        Protocol::V2 => Ok(SwapRoute::V2(
+            best_route(client, dex, token_in, token_out, amount_in).await?,
+        )),
+        Protocol::V3 => Ok(SwapRoute::V3(
+            best_route_v3(client, network::v3()?, token_in, token_out, amount_in).await?,
+        )),
+        Protocol::Best => {
+            let (v2, v3) = quote_both(client, dex, token_in, token_out, amount_in).await;
+            larger(v2, v3)
+        }
+    }
+}
+
+async fn quote_both<M: Middleware + 'static>(
+    client: Arc<M>,
+    dex: &Dex,
+    token_in: Address,
+    token_out: Address,
+    amount_in: U256,
+) -> (Result<Route>, Result<V3Route>) {
+    let v2 = best_route(client.clone(), dex, token_in, token_out, amount_in).await;
+    let v3 = match network::v3() {
+        Ok(v3) => best_route_v3(client, v3, token_in, token_out, amount_in).await,
+        Err(e) => Err(e.into()),
+    };
+    (v2, v3)
+}
+
+// Route with the larger output, or the one that exists
+fn larger(v2: Result<Route>, v3: Result<V3Route>) -> Result<SwapRoute> {
+    match (v2, v3) {
+        (Ok(v2), Ok(v3)) if v3.amount_out > v2.amount_out() => Ok(SwapRoute::V3(v3)),
+        (Ok(v2), _) => Ok(SwapRoute::V2(v2)),
+        (Err(_), Ok(v3)) => Ok(SwapRoute::V3(v3)),
+        (Err(e), Err(_)) => Err(e),
+    }
+}
+
+pub async fn print_swap_route(route: &SwapRoute) -> Result<()> {
+    match route {
+        SwapRoute::V2(route) => {
+            output::field("protocol", "v2");
+            print_route(route).await
+        }
+        SwapRoute::V3(route) => {
+            output::field("protocol", "v3");
+            print_route_v3(route).await
+        }
+    }
+}
+
+// Reserves of every hop of the path on the dex, ordered as (input token, output token)
+pub async fn path_reserves<M: Middleware + 'static>(
+    client: Arc<M>,
+    dex: &Dex,
+    path: &[Address],
+) -> Result<Vec<(U256, U256)>> {
+    let mut reserves = Vec::new();
+    for hop in path.windows(2) {
+        let pair_addr = get_pair(client.clone(), dex, hop[0], hop[1]).await?;
+        if pair_addr.is_zero() {
+            return Err(Error::Aborted(format!(
+                "No {} pool for {:?} and {:?}",
+                dex.name, hop[0], hop[1]
+            ))
+            .into());
+        }
+        let pair = IUniswapPair::new(pair_addr, client.clone());
+        reserves.push(get_reserves(&pair, hop[0]).await?);
+    }
+    Ok(reserves)
+}
+
+// --max-impact in percent, a finite number from 0 to 100
+pub fn parse_max_impact(input: &str) -> Result<f64, String> {
+    match input.trim().parse::<f64>() {
+        Ok(percent) if percent.is_finite() && (0.0..=100.0).contains(&percent) => Ok(percent),
+        _ => Err(format!(
+            "invalid price impact '{}', expected a percentage from 0 to 100",
+            input
+        )),
+    }
+}
+
+// Price impact of the route of a swap, V2 from the pool reserves and V3 from the pool spot prices
+pub async fn check_swap_impact<M: Middleware + 'static>(
+    client: Arc<M>,
+    dex: &Dex,
+    route: &SwapRoute,
+) -> Result<()> {
+    match route {
+        SwapRoute::V2(route) => check_price_impact(client, dex, route, false).await,
+        SwapRoute::V3(route) => {
+            let prices = spot_prices(client, network::v3()?, route).await?;
+            report_price_impact(&route.path, route.amount_in, route.amount_out, |amount| {
+                mid_amount_out_v3(amount, &prices)
+            })
+            .await
+        }
+    }
+}
+
+// Price impact of a V2 route from local AMM math on the pool reserves, refused above --max-impact
+pub async fn check_price_impact<M: Middleware + 'static>(
+    client: Arc<M>,
+    dex: &Dex,
+    route: &Route,
+    exact_out: bool,
+) -> Result<()> {
+    let reserves = path_reserves(client, dex, &route.path).await?;
+    let amounts = if exact_out {
+        get_amounts_in(route.amount_out(), &reserves, dex.fee_bps)?
+    } else {
+        get_amounts_out(route.amount_in(), &reserves, dex.fee_bps)?
+    };
+    if amounts != route.amounts {
+        text!(
+            "{}",
+            format!(
+                "Local AMM math differs from the {} router quote, check fee_bps of the dex.",
+                dex.name
+            )
+            .yellow()
+        );
+    }
+    report_price_impact(
+        &route.path,
+        amounts[0],
+        amounts[amounts.len() - 1],
+        |amount| mid_amount_out(amount, &reserves),
+    )
+    .await
+}
+
+// Mid price, effective price and impact of a swap, mid gives the output of an amount at the mid
+// price of the path
+async fn report_price_impact(
+    path: &[Address],
+    amount_in: U256,
+    amount_out: U256,
+    mid: impl Fn(U256) -> U256,
+) -> Result<()> {
+    //prices of one input token in output tokens
+    let last = path.len() - 1;
+    let (decimals_in, symbol_in, _) = get_token_metadata(&format!("{:?}", path[0])).await?;
+    let (decimals_out, symbol_out, _) = get_token_metadata(&format!("{:?}", path[last])).await?;
+    let unit = pow10(decimals_in)?;
+    let mid_price = mid(unit);
+    let effective_price =
+        U256::try_from(amount_out.full_mul(unit) / U512::from(amount_in)).unwrap_or(U256::MAX);
+    let impact = shortfall_bps(mid(amount_in), amount_out);
+    let max_impact = settings::get().max_impact;
+    let max_bps = (max_impact * 100.0).round() as u64;
+
+    output::field(
+        "priceImpact",
+        json!({
+            "bps": impact,
+            "maxBps": max_bps,
+            "midPrice": output::amount(mid_price, decimals_out),
+            "effectivePrice": output::amount(effective_price, decimals_out),
+        }),
+    );
+    text!(
+        "Mid price: 1 {} = {} {}",
+        symbol_in,
+        format_units(mid_price, decimals_out, Some(8)),
+        symbol_out
+    );
+    text!(
+        "Effective price: 1 {} = {} {}",
+        symbol_in,
+        format_units(effective_price, decimals_out, Some(8)),
+        symbol_out
+    );
+    let impact_text = format!("{}.{:02} %", impact / 100, impact % 100);
+    if impact > max_bps {
+        text!("Price impact: {} (max {} %)", impact_text.red(), max_impact);
+        return Err(Error::Aborted(format!(
+            "price impact {} is above --max-impact {} %",
+            impact_text, max_impact
+        ))
+        .into());
+    }
+    text!("Price impact: {} (max {} %)", impact_text, max_impact);
+
+    Ok(())
+}
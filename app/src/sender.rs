@@ -0,0 +1,277 @@
+use colored::Colorize;
+use ethers::{
+    abi::{Detokenize, Function},
+    contract::builders::ContractCall,
+    core::types::{transaction::eip2718::TypedTransaction, TransactionReceipt, U256, U64},
+    providers::{Middleware, ProviderError},
+};
+use eyre::Result;
+use serde_json::{json, Map, Value};
+use std::sync::Arc;
+
+use crate::{
+    deadline,
+    error::Error,
+    fee,
+    loader::{start_loader, stop_loader},
+    output::{self, text},
+    settings,
+    utils::to_human_readable,
+};
+
+// Expected change of a balance, shown by --dry-run
+pub struct Delta {
+    pub symbol: String,
+    pub decimals: u8,
+    pub amount: U256,
+    pub incoming: bool,
+}
+
+impl Delta {
+    pub fn gain(symbol: &str, decimals: u8, amount: U256) -> Self {
+        Delta {
+            symbol: symbol.to_string(),
+            decimals,
+            amount,
+            incoming: true,
+        }
+    }
+    pub fn spend(symbol: &str, decimals: u8, amount: U256) -> Self {
+        Delta {
+            symbol: symbol.to_string(),
+            decimals,
+            amount,
+            incoming: false,
+        }
+    }
+}
+
+// Send the contract call and wait for its receipt, with --dry-run only simulate it
+pub async fn send_call<M: Middleware + 'static, D: Detokenize>(
+    client: &Arc<M>,
+    call: ContractCall<M, D>,
+    message: &str,
+    deltas: &[Delta],
+) -> Result<Option<TransactionReceipt>> {
+    if settings::get().dry_run {
+        simulate(client, &call.tx, Some(&call.function), deltas).await?;
+        return Ok(None);
+    }
+
+    broadcast(client, call.tx, message).await
+}
+
+// Same as send_call for a raw transaction
+pub async fn send_tx<M: Middleware + 'static>(
+    client: &Arc<M>,
+    tx: TypedTransaction,
+    message: &str,
+    deltas: &[Delta],
+) -> Result<Option<TransactionReceipt>> {
+    if settings::get().dry_run {
+        simulate(client, &tx, None, deltas).await?;
+        return Ok(None);
+    }
+
+    broadcast(client, tx, message).await
+}
+
+// Send with the fees of the --fee strategy once the base fee is under --max-base-fee, and wait
+// for the receipt
+async fn broadcast<M: Middleware + 'static>(
+    client: &Arc<M>,
+    mut tx: TypedTransaction,
+    message: &str,
+) -> Result<Option<TransactionReceipt>> {
+    fee::wait_for_base_fee(client).await?;
+    fee::apply(client, &mut tx).await?;
+    let loader = start_loader(message);
+    let pending = client.send_transaction(tx, None).await;
+    let receipt = match pending {
+        Ok(pending) => pending.await,
+        Err(e) => {
+            stop_loader(loader);
+            return Err(send_error(e.to_string()).into());
+        }
+    };
+    stop_loader(loader);
+    check_receipt(client, receipt).await
+}
+
+// The gas estimation of a transaction past its deadline reverts before anything is sent
+fn send_error(error: String) -> Error {
+    if deadline::is_expired(&error) {
+        return deadline::expired_error(None);
+    }
+    Error::from_rpc(error)
+}
+
+// A mined transaction with status 0 reverted, a missing receipt was dropped from the mempool
+async fn check_receipt<M: Middleware + 'static>(
+    client: &Arc<M>,
+    receipt: Result<Option<TransactionReceipt>, ProviderError>,
+) -> Result<Option<TransactionReceipt>> {
+    let receipt = receipt
+        .map_err(|e| Error::Rpc(e.to_string()))?
+        .ok_or_else(|| Error::Rpc("transaction dropped from the mempool".to_string()))?;
+    if receipt.status == Some(U64::zero()) {
+        //a receipt has no revert reason, an expired deadline is told by the block timestamp
+        if let Some(block_number) = receipt.block_number {
+            if let Ok(Some(block)) = client.get_block(block_number).await {
+                let timestamp = block.timestamp.as_u64();
+                if deadline::was_expired(timestamp) {
+                    return Err(deadline::expired_error(Some(timestamp)).into());
+                }
+            }
+        }
+        return Err(Error::Revert(format!(
+            "transaction {:?} reverted in block {}",
+            receipt.transaction_hash,
+            receipt.block_number.unwrap_or_default()
+        ))
+        .into());
+    }
+    Ok(Some(receipt))
+}
+
+// eth_call and eth_estimateGas of the transaction, nothing is broadcast
+async fn simulate<M: Middleware + 'static>(
+    client: &Arc<M>,
+    tx: &TypedTransaction,
+    function: Option<&Function>,
+    deltas: &[Delta],
+) -> Result<()> {
+    let block = settings::block_id();
+    let mut simulation = Map::new();
+    simulation.insert("block".to_string(), json!(settings::get().block));
+    simulation.insert("to".to_string(), json!(tx.to_addr()));
+    simulation.insert(
+        "function".to_string(),
+        json!(function.map(|f| f.signature())),
+    );
+
+    text!("{}", "\n===== DRY RUN =====\n".bold().yellow());
+    text!(
+        "Block: {}",
+        settings::get()
+            .block
+            .map_or("latest".to_string(), |b| b.to_string())
+    );
+    if let Some(to) = tx.to_addr() {
+        text!("To: {:?}", to);
+    }
+    if let Some(function) = function {
+        text!("Function: {}", function.signature());
+    }
+    let value = tx.value().copied().unwrap_or_default();
+    simulation.insert("value".to_string(), output::amount(value, 18));
+    if !value.is_zero() {
+        text!(
+            "Value: {} ETH ({} wei)",
+            to_human_readable(value, 18),
+            value
+        );
+    }
+
+    match client.call(tx, block).await {
+        Ok(bytes) => {
+            simulation.insert("success".to_string(), json!(true));
+            text!("{}", "Simulation succeeded".green());
+            if let Some(function) = function {
+                let tokens = function.decode_output(&bytes)?;
+                simulation.insert("returns".to_string(), json!(format!("{:?}", tokens)));
+                if !tokens.is_empty() {
+                    text!("Returns: {:?}", tokens);
+                }
+            }
+        }
+        Err(e) => {
+            let mut reason = revert_reason(&e.to_string());
+            if deadline::is_expired(&reason) {
+                reason = deadline::expired_error(None).to_string();
+            }
+            simulation.insert("success".to_string(), json!(false));
+            simulation.insert("revertReason".to_string(), json!(reason));
+            text!("{} {}", "Simulation reverted:".red(), reason);
+        }
+    }
+
+    match client.estimate_gas(tx, block).await {
+        Ok(gas) => {
+            //worst case cost, the max fee of the --fee strategy
+            let gas_price = fee::get(client).await?.max_fee();
+            simulation.insert("gasEstimate".to_string(), json!(gas.to_string()));
+            simulation.insert("gasPrice".to_string(), json!(gas_price.to_string()));
+            simulation.insert("gasCost".to_string(), output::amount(gas * gas_price, 18));
+            text!(
+                "Gas estimate: {} (~{} ETH at most, max fee {} gwei)",
+                gas,
+                to_human_readable(gas * gas_price, 18),
+                to_human_readable(gas_price, 9)
+            );
+        }
+        Err(e) => {
+            let reason = revert_reason(&e.to_string());
+            simulation.insert("gasError".to_string(), json!(reason));
+            text!("{} {}", "Gas estimation failed:".red(), reason);
+        }
+    }
+
+    let changes: Vec<Value> = deltas
+        .iter()
+        .map(|delta| {
+            json!({
+                "symbol": delta.symbol,
+                "direction": if delta.incoming { "in" } else { "out" },
+                "amount": output::amount(delta.amount, delta.decimals),
+            })
+        })
+        .collect();
+    simulation.insert("balanceChanges".to_string(), Value::Array(changes));
+    output::push("simulations", simulation);
+
+    if !deltas.is_empty() {
+        text!("Expected balance changes:");
+        for delta in deltas {
+            text!(
+                "  {}{} {}",
+                if delta.incoming { "+" } else { "-" },
+                to_human_readable(delta.amount, delta.decimals),
+                delta.symbol
+            );
+        }
+    }
+
+    Ok(())
+}
+
+// Keep the "execution reverted: ..." part of a JSON-RPC error
+pub fn revert_reason(error: &str) -> String {
+    match error.find("execution reverted") {
+        Some(start) => {
+            let reason = &error[start..];
+            let end = reason.find(", data:").unwrap_or(reason.len());
+            reason[..end].trim_end_matches(')').to_string()
+        }
+        None => error.to_string(),
+    }
+}
+
+pub fn print_receipt(label: &str, receipt: &Option<TransactionReceipt>) -> Result<()> {
+    //nothing was sent with --dry-run
+    if settings::get().dry_run {
+        return Ok(());
+    }
+    if let Some(receipt) = receipt {
+        output::push("receipts", output::receipt(label, receipt));
+    }
+    let receipt_json: serde_json::Value = serde_json::to_value(receipt)?;
+    text!(
+        "{} Receipt: {}",
+        label,
+        receipt_json["transactionHash"]
+            .as_str()
+            .unwrap_or("Unknown hash")
+    );
+    Ok(())
+}
@@ -0,0 +1,44 @@
+use ethers::core::types::{BlockId, BlockNumber};
+use std::sync::OnceLock;
+
+use crate::{
+    deadline::Duration,
+    fee::{Gwei, Strategy},
+    output::Format,
+};
+
+// Global options shared by every command, set once from the command line in main
+#[derive(Debug, Default, Clone)]
+pub struct Settings {
+    pub dry_run: bool,
+    pub block: Option<u64>,
+    pub output: Format,
+    // Largest price impact accepted for a swap, in percent
+    pub max_impact: f64,
+    // Time given to a transaction after the reference block before the router rejects it
+    pub deadline: Duration,
+    // Safety margin added to the gas kept when sending the whole ETH balance, in percent
+    pub gas_margin: u64,
+    // Fee preset, overrides and cap applied to every transaction sent
+    pub fee: Strategy,
+    // Base fee a transaction waits for before it is sent
+    pub max_base_fee: Option<Gwei>,
+    pub max_base_fee_timeout: Duration,
+}
+
+static SETTINGS: OnceLock<Settings> = OnceLock::new();
+
+pub fn init(settings: Settings) {
+    SETTINGS.set(settings).ok();
+}
+
+pub fn get() -> &'static Settings {
+    SETTINGS.get_or_init(Settings::default)
+}
+
+// Block of the reads and simulations, --block (dry runs only) or latest when None
+pub fn block_id() -> Option<BlockId> {
+    get()
+        .block
+        .map(|b| BlockId::Number(BlockNumber::Number(b.into())))
+}
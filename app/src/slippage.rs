@@ -0,0 +1,113 @@
+use colored::Colorize;
+use ethers::core::types::U256;
+use std::fmt;
+use std::str::FromStr;
+
+use crate::{
+    output::text,
+    utils::{parse_units, to_human_readable},
+};
+
+const BPS: u64 = 10000;
+
+// Slippage tolerance in basis points, given as a percentage ("0.5", "0.5%") or in bps ("50bps")
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Slippage(u64);
+
+impl Slippage {
+    pub fn bps(self) -> u64 {
+        self.0
+    }
+
+    // Smallest output accepted for an exact input amount, rounded down
+    pub fn min_out(self, amount: U256) -> U256 {
+        amount * U256::from(BPS - self.0) / U256::from(BPS)
+    }
+
+    // Largest input accepted for an exact output amount, rounded up
+    pub fn max_in(self, amount: U256) -> U256 {
+        (amount * U256::from(BPS + self.0) + U256::from(BPS - 1)) / U256::from(BPS)
+    }
+}
+
+impl FromStr for Slippage {
+    type Err = String;
+
+    fn from_str(input: &str) -> Result<Self, Self::Err> {
+        let input = input.trim();
+        let bps = match input.strip_suffix("bps") {
+            Some(bps) => U256::from_dec_str(bps.trim()).map_err(|_| invalid(input))?,
+            None => {
+                let percent = input.strip_suffix('%').unwrap_or(input).trim();
+                parse_units(percent, 2).map_err(|_| invalid(input))?
+            }
+        };
+        if bps >= U256::from(BPS) {
+            return Err(format!("slippage '{}' must be below 100%", input));
+        }
+        Ok(Slippage(bps.as_u64()))
+    }
+}
+
+fn invalid(input: &str) -> String {
+    format!(
+        "invalid slippage '{}', expected a percentage (0.5) or bps (50bps)",
+        input
+    )
+}
+
+impl fmt::Display for Slippage {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}.{:02} %", self.0 / 100, self.0 % 100)
+    }
+}
+
+// Amount guaranteed by the slippage bound, printed before the transaction is submitted
+pub fn print_bound(label: &str, amount: U256, decimals: u8, symbol: &str) {
+    text!(
+        "{} {} {} ({} wei)",
+        format!("{}:", label).bold(),
+        to_human_readable(amount, decimals),
+        symbol,
+        amount
+    );
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn slippage(input: &str) -> Slippage {
+        input.parse().unwrap()
+    }
+
+    #[test]
+    fn parses_percentages_and_bps() {
+        assert_eq!(slippage("0.5").bps(), 50);
+        assert_eq!(slippage("0.5%").bps(), 50);
+        assert_eq!(slippage("50bps").bps(), 50);
+        assert_eq!(slippage("3").bps(), 300);
+        assert_eq!(slippage("0.5").to_string(), "0.50 %");
+        assert!("100".parse::<Slippage>().is_err());
+        assert!("10000bps".parse::<Slippage>().is_err());
+        assert!("0.005".parse::<Slippage>().is_err());
+        assert!("abc".parse::<Slippage>().is_err());
+    }
+
+    #[test]
+    fn min_out_rounds_down() {
+        assert_eq!(slippage("0.5").min_out(1000.into()), U256::from(995));
+        assert_eq!(slippage("0.5").min_out(1001.into()), U256::from(995));
+        assert_eq!(slippage("0.5").min_out(1.into()), U256::zero());
+        assert_eq!(slippage("0").min_out(1001.into()), U256::from(1001));
+    }
+
+    #[test]
+    fn max_in_rounds_up() {
+        assert_eq!(slippage("0.5").max_in(1000.into()), U256::from(1005));
+        assert_eq!(slippage("0.5").max_in(1001.into()), U256::from(1007));
+        assert_eq!(slippage("0.5").max_in(1.into()), U256::from(2));
+        assert_eq!(slippage("0.5").max_in(200.into()), U256::from(201));
+        assert_eq!(slippage("0").max_in(1001.into()), U256::from(1001));
+    }
+}
@@ -0,0 +1,61 @@
+use colored::Colorize;
+use ethers::{contract::builders::ContractCall, core::types::U256, providers::Middleware};
+use eyre::Result;
+use serde_json::json;
+
+use crate::{
+    error::Error,
+    output::{self, text},
+    utils::at_block,
+};
+
+// Stop the search once the bounds are within 1 bps of the quote
+const PRECISION_BPS: u64 = 1;
+
+// Fee-on-transfer tokens deliver less than getAmountsOut quotes. The ...SupportingFeeOnTransferTokens
+// router functions check the real balance delta against amountOutMin, so simulating them with
+// eth_call and binary searching amountOutMin gives the amount that would actually be received.
+pub async fn measure_output<M, F>(quoted: U256, simulate: F) -> Result<U256>
+where
+    M: Middleware + 'static,
+    F: Fn(U256) -> ContractCall<M, ()>,
+{
+    if at_block(simulate(quoted)).call().await.is_ok() {
+        return Ok(quoted);
+    }
+    if let Err(e) = at_block(simulate(U256::zero())).call().await {
+        //reverts even without a minimum output, the revert reason tells why
+        return Err(Error::from_rpc(format!("swap simulation failed: {}", e)).into());
+    }
+
+    let precision = (quoted * U256::from(PRECISION_BPS) / U256::from(10000)).max(U256::one());
+    let mut low = U256::zero();
+    let mut high = quoted;
+    while high - low > precision {
+        let mid = (low + high) / 2;
+        if at_block(simulate(mid)).call().await.is_ok() {
+            low = mid;
+        } else {
+            high = mid;
+        }
+    }
+
+    Ok(low)
+}
+
+pub fn tax_bps(quoted: U256, received: U256) -> u64 {
+    if quoted.is_zero() || received >= quoted {
+        return 0;
+    }
+    ((quoted - received) * U256::from(10000) / quoted).as_u64()
+}
+
+pub fn print_tax(label: &str, bps: u64) {
+    output::field("tax", json!({ "label": label, "bps": bps }));
+    text!(
+        "{} {}.{:02} %",
+        format!("{} tax:", label).yellow(),
+        bps / 100,
+        bps % 100
+    );
+}
@@ -0,0 +1,278 @@
+use colored::Colorize;
+use ethers::{
+    contract::abigen,
+    core::types::{Address, BlockId, BlockNumber, U256},
+    providers::Middleware,
+};
+use eyre::{eyre, Result};
+use serde_json::json;
+use std::sync::Arc;
+abigen!(IUniswapPool, "./abi/pool_abi.json");
+
+use crate::{
+    alias,
+    client::get_client,
+    error::Error,
+    network::{self, Dex},
+    output::{self, text},
+    router::get_pair,
+    settings,
+    utils::{get_token_metadata, to_f64},
+};
+
+// Number of blocks used to estimate the average block time
+const BLOCK_TIME_SAMPLE: u64 = 100;
+
+#[derive(Debug, Clone)]
+pub struct Twap {
+    // Prices are UQ112x112 fixed point: token0 in token1 and token1 in token0 (raw units)
+    pub price_0: U256,
+    pub price_1: U256,
+    pub spot_0: U256,
+    pub spot_1: U256,
+    pub token_0: Address,
+    pub token_1: Address,
+    pub start_block: u64,
+    pub end_block: u64,
+    pub elapsed: u64,
+}
+
+// Convert a UQ112x112 raw price into a human price using the decimals of both tokens
+pub fn uq112_to_f64(price: U256, decimals_base: u8, decimals_quote: u8) -> f64 {
+    to_f64(price, 0) / 2f64.powi(112) * 10f64.powi(decimals_base as i32 - decimals_quote as i32)
+}
+
+fn spot_prices(reserve_0: u128, reserve_1: u128) -> (U256, U256) {
+    if reserve_0 == 0 || reserve_1 == 0 {
+        return (U256::zero(), U256::zero());
+    }
+    let reserve_0 = U256::from(reserve_0);
+    let reserve_1 = U256::from(reserve_1);
+    (
+        (reserve_1 << 112) / reserve_0,
+        (reserve_0 << 112) / reserve_1,
+    )
+}
+
+// Same as UniswapV2OracleLibrary.currentCumulativePrices, evaluated at a past block
+async fn cumulative_prices<M: Middleware + 'static>(
+    pool: &IUniswapPool<M>,
+    block: u64,
+    timestamp: u64,
+) -> Result<(U256, U256, u128, u128)> {
+    let block_id = BlockId::Number(BlockNumber::Number(block.into()));
+    let mut price_0 = pool
+        .price_0_cumulative_last()
+        .block(block_id)
+        .call()
+        .await?;
+    let mut price_1 = pool
+        .price_1_cumulative_last()
+        .block(block_id)
+        .call()
+        .await?;
+    let (reserve_0, reserve_1, last_update) = pool.get_reserves().block(block_id).call().await?;
+
+    //the accumulators only move on swaps, add the time elapsed since the last one
+    let elapsed = (timestamp as u32).wrapping_sub(last_update);
+    if elapsed > 0 {
+        let (spot_0, spot_1) = spot_prices(reserve_0, reserve_1);
+        price_0 = price_0.overflowing_add(spot_0 * U256::from(elapsed)).0;
+        price_1 = price_1.overflowing_add(spot_1 * U256::from(elapsed)).0;
+    }
+
+    Ok((price_0, price_1, reserve_0, reserve_1))
+}
+
+async fn block_timestamp<M: Middleware + 'static>(client: &Arc<M>, block: u64) -> Result<u64> {
+    let block = client
+        .get_block(block)
+        .await
+        .map_err(|e| eyre!("{}", e))?
+        .ok_or_else(|| eyre!("Block {} not found", block))?;
+    Ok(block.timestamp.as_u64())
+}
+
+pub async fn get_twap<M: Middleware + 'static>(
+    client: Arc<M>,
+    pair_addr: Address,
+    window: u64,
+) -> Result<Twap> {
+    let pool = IUniswapPool::new(pair_addr, client.clone());
+
+    //find the block at the start of the window from the average block rate
+    let end_block = match settings::get().block {
+        Some(block) => block,
+        None => client
+            .get_block_number()
+            .await
+            .map_err(|e| eyre!("{}", e))?
+            .as_u64(),
+    };
+    let end_time = block_timestamp(&client, end_block).await?;
+    let sample_block = end_block.saturating_sub(BLOCK_TIME_SAMPLE);
+    let sample_time = block_timestamp(&client, sample_block).await?;
+    //blocks per second from the sample, exact for sub-second block times (Arbitrum)
+    let sample_blocks = (end_block - sample_block) as u128;
+    let sample_seconds = end_time.saturating_sub(sample_time).max(1) as u128;
+    let window_blocks = (window as u128 * sample_blocks / sample_seconds).max(1);
+    let start_block = end_block.saturating_sub(u64::try_from(window_blocks).unwrap_or(u64::MAX));
+    let start_time = block_timestamp(&client, start_block).await?;
+
+    let (start_0, start_1, _, _) = cumulative_prices(&pool, start_block, start_time).await?;
+    let (end_0, end_1, reserve_0, reserve_1) =
+        cumulative_prices(&pool, end_block, end_time).await?;
+    let elapsed = end_time - start_time;
+    if elapsed == 0 {
+        return Err(Error::Input("TWAP window is too short".to_string()).into());
+    }
+
+    //accumulators are meant to overflow, the difference stays correct
+    let price_0 = end_0.overflowing_sub(start_0).0 / U256::from(elapsed);
+    let price_1 = end_1.overflowing_sub(start_1).0 / U256::from(elapsed);
+    let (spot_0, spot_1) = spot_prices(reserve_0, reserve_1);
+
+    Ok(Twap {
+        price_0,
+        price_1,
+        spot_0,
+        spot_1,
+        token_0: pool.token_0().call().await?,
+        token_1: pool.token_1().call().await?,
+        start_block,
+        end_block,
+        elapsed,
+    })
+}
+
+// Compare spot and TWAP price along a swap path, cancel when it deviates more than max_deviation %
+pub async fn check_route_twap<M: Middleware + 'static>(
+    client: Arc<M>,
+    dex: &Dex,
+    path: &[Address],
+    window: u64,
+    max_deviation: f64,
+) -> Result<()> {
+    let mut spot = 1.0;
+    let mut twap = 1.0;
+    for hop in path.windows(2) {
+        let pair_addr = get_pair(client.clone(), dex, hop[0], hop[1]).await?;
+        //a V3 route can go through tokens without a V2 pool, there is no TWAP to compare with
+        if pair_addr.is_zero() {
+            let (_, symbol_in, _) = get_token_metadata(&format!("{:?}", hop[0])).await?;
+            let (_, symbol_out, _) = get_token_metadata(&format!("{:?}", hop[1])).await?;
+            return Err(Error::Aborted(format!(
+                "no {} V2 pool for the hop {} -> {}, the TWAP guard cannot check this route",
+                dex.name, symbol_in, symbol_out
+            ))
+            .into());
+        }
+        let hop_twap = get_twap(client.clone(), pair_addr, window).await?;
+        if hop[0] == hop_twap.token_0 {
+            spot *= uq112_to_f64(hop_twap.spot_0, 0, 0);
+            twap *= uq112_to_f64(hop_twap.price_0, 0, 0);
+        } else {
+            spot *= uq112_to_f64(hop_twap.spot_1, 0, 0);
+            twap *= uq112_to_f64(hop_twap.price_1, 0, 0);
+        }
+    }
+
+    let deviation = (spot / twap - 1.0).abs() * 100.0;
+    output::field(
+        "twapGuard",
+        json!({ "deviation": deviation, "maxDeviation": max_deviation, "window": window }),
+    );
+    text!(
+        "Spot deviates {:.2} % from the {}s TWAP (max {} %)",
+        deviation,
+        window,
+        max_deviation
+    );
+    if deviation > max_deviation {
+        return Err(Error::Aborted("price deviates too much from TWAP".to_string()).into());
+    }
+
+    Ok(())
+}
+
+#[tokio::main]
+pub async fn twap(token_a: &String, token_b: &Option<String>, window: &u64) -> Result<()> {
+    text!("{}", "\n===== TWAP =====\n".bold().blue());
+    //client
+    let client = get_client().await?;
+
+    //params: two tokens resolved through the factory, or the pair address itself
+    let pair_addr = match token_b {
+        Some(token_b) => {
+            let token_0 = alias::alias_token(token_a)?;
+            let token_1 = alias::alias_token(token_b)?;
+            let dex = network::dex(network::DEFAULT_DEX)?;
+            get_pair(client.clone(), dex, token_0, token_1).await?
+        }
+        None => alias::alias_address(token_a)?,
+    };
+    if pair_addr.is_zero() {
+        output::field("pair", Option::<Address>::None);
+        text!("{}", "No UniswapV2 pool for this pair.".red());
+        return Ok(());
+    }
+
+    let twap = get_twap(client, pair_addr, *window).await?;
+    let (decimals_0, symbol_0, _) = get_token_metadata(&format!("{:?}", twap.token_0)).await?;
+    let (decimals_1, symbol_1, _) = get_token_metadata(&format!("{:?}", twap.token_1)).await?;
+    output::field("pair", pair_addr);
+    output::field(
+        "token0",
+        json!({ "address": twap.token_0, "symbol": symbol_0 }),
+    );
+    output::field(
+        "token1",
+        json!({ "address": twap.token_1, "symbol": symbol_1 }),
+    );
+    output::field(
+        "window",
+        json!({ "seconds": twap.elapsed, "startBlock": twap.start_block, "endBlock": twap.end_block }),
+    );
+    output::field(
+        "twap",
+        json!({
+            "price0": uq112_to_f64(twap.price_0, decimals_0, decimals_1),
+            "price1": uq112_to_f64(twap.price_1, decimals_1, decimals_0),
+        }),
+    );
+    output::field(
+        "spot",
+        json!({
+            "price0": uq112_to_f64(twap.spot_0, decimals_0, decimals_1),
+            "price1": uq112_to_f64(twap.spot_1, decimals_1, decimals_0),
+        }),
+    );
+
+    text!("Pair: {:?}", pair_addr);
+    text!(
+        "Window: {}s (blocks {} -> {})",
+        twap.elapsed,
+        twap.start_block,
+        twap.end_block
+    );
+    text!(
+        "TWAP: \n1 {} = {} {}\n1 {} = {} {}",
+        symbol_0,
+        uq112_to_f64(twap.price_0, decimals_0, decimals_1),
+        symbol_1,
+        symbol_1,
+        uq112_to_f64(twap.price_1, decimals_1, decimals_0),
+        symbol_0
+    );
+    text!(
+        "Spot: \n1 {} = {} {}\n1 {} = {} {}",
+        symbol_0,
+        uq112_to_f64(twap.spot_0, decimals_0, decimals_1),
+        symbol_1,
+        symbol_1,
+        uq112_to_f64(twap.spot_1, decimals_1, decimals_0),
+        symbol_0
+    );
+
+    Ok(())
+}
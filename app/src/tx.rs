@@ -8,45 +8,63 @@ use ethers::{
 use eyre::Result;
 abigen!(IUniswapRouter, "./abi/router_univ2.json");
 abigen!(IERC20, "./abi/erc20_abi.json");
-abigen!(IWETH, "./abi/weth_abi.json");
 
 use crate::{
+    alias,
+    amount::{is_relative, parse_amount, parse_exact},
     client::{get_client, get_client_signed, print_state},
-    loader::{start_loader, stop_loader},
-    utils::{get_token_metadata, get_valid_timestamp, to_human_readable},
+    deadline,
+    error::Error,
+    gas::{self, print_reserve},
+    network,
+    output::{self, text},
+    router::{
+        best_route, best_route_exact_out, check_price_impact, check_swap_impact, pick_route,
+        print_route, print_swap_route, select_route, Protocol, SwapRoute,
+    },
+    sender::{print_receipt, revert_reason, send_call, send_tx, Delta},
+    settings,
+    slippage::{print_bound, Slippage},
+    tax::{measure_output, print_tax, tax_bps},
+    twap::check_route_twap,
+    utils::{at_block, get_token_metadata, to_human_readable},
+    v3::{swap_call, ISwapRouter},
 };
-#[path = "alias.rs"]
-mod alias;
 
 #[tokio::main]
+#[allow(clippy::too_many_arguments)]
 pub async fn swap_tokens(
     token_in_a: &String,
     token_out_a: &String,
-    amount: &String,
-    slippage: &f64,
+    amount: &str,
+    slippage: &Slippage,
+    twap_guard: &Option<f64>,
+    twap_window: &u64,
+    dex: &str,
+    protocol: &Protocol,
 ) -> Result<()> {
     let client_signed = get_client_signed().await?;
     let account = client_signed.address();
     print_state(&client_signed).await?;
 
-    println!("{}", "\n===== SWAP TOKENS =====\n".bold().blue());
+    text!("{}", "\n===== SWAP TOKENS =====\n".bold().blue());
 
     //Check for alias params
-    let token_in = alias::alias_token(token_in_a).unwrap();
-    let token_out = alias::alias_token(token_out_a).unwrap();
-    let (decimals_token_in, symbol_token_in, _) = get_token_metadata(&token_in_a).await?;
-    let (decimals_token_out, symbol_token_out, _) = get_token_metadata(&token_out_a).await?;
+    let token_in = alias::alias_token(token_in_a)?;
+    let token_out = alias::alias_token(token_out_a)?;
+    let (decimals_token_in, symbol_token_in, _) = get_token_metadata(token_in_a).await?;
+    let (decimals_token_out, symbol_token_out, _) = get_token_metadata(token_out_a).await?;
 
     //params
-    let router_addr = "0x7a250d5630B4cF539739dF2C5dAcb4c659F2488D".parse::<Address>()?;
+    let dex = network::dex(dex)?;
+    let router_addr = dex.router;
     let router = IUniswapRouter::new(router_addr, client_signed.clone());
     let token_contract = IERC20::new(token_in, client_signed.clone());
 
-    let valid_timestamp = get_valid_timestamp(300000);
-    let path = vec![token_in, token_out];
+    let deadline = deadline::get(&client_signed).await?;
 
-    let balance_of = token_contract.balance_of(account).call().await?;
-    println!(
+    let balance_of = at_block(token_contract.balance_of(account)).call().await?;
+    text!(
         "{} {} ({}) {}",
         "Actual Balance:".bold(),
         to_human_readable(balance_of, decimals_token_in),
@@ -54,37 +72,52 @@ pub async fn swap_tokens(
         symbol_token_in
     );
 
-    let amount_in = if amount == "max" {
-        balance_of
-    } else {
-        U256::from_dec_str(amount).unwrap()
-    };
-
-    if balance_of.is_zero() {
-        println!("{}", "Balance is 0. Transaction cancelled.".red());
-        return Ok(());
-    }
-
-    if amount_in > balance_of {
-        println!(
-            "{} {} ({}) {} ({})",
-            "Amount".red(),
-            amount_in,
-            "is greater than the balance".red(),
-            balance_of,
-            "Transaction cancelled.".red()
-        );
-        return Ok(());
+    let amount_in = parse_amount(amount, decimals_token_in, balance_of)?;
+
+    check_balance(balance_of, amount_in)?;
+
+    let route = select_route(
+        client_signed.clone(),
+        *protocol,
+        dex,
+        token_in,
+        token_out,
+        amount_in,
+        decimals_token_out,
+    )
+    .await?;
+    print_swap_route(&route).await?;
+    check_swap_impact(client_signed.clone(), dex, &route).await?;
+    let amount_out = route.amount_out();
+
+    //optional manipulation check against the V2 pools TWAP, also for a V3 route
+    if let Some(max_deviation) = twap_guard {
+        check_route_twap(
+            client_signed.clone(),
+            dex,
+            route.path(),
+            *twap_window,
+            *max_deviation,
+        )
+        .await?;
     }
 
-    let amounts_out = router.get_amounts_out(amount_in, path).call().await?;
+    let amount_out_min = slippage.min_out(amount_out);
 
-    let slippage_decimal = slippage / 100.0;
-    let amount_out_min = amounts_out[1]
-        - (amounts_out[1] * U256::from((slippage_decimal * 1e18) as u64) / U256::from(1e18 as u64));
-
-    println!("Slippage: {} %", slippage);
-    println!(
+    output::field("slippageBps", slippage.bps());
+    output::field("amountIn", output::amount(amount_in, decimals_token_in));
+    output::field(
+        "amountOutMin",
+        output::amount(amount_out_min, decimals_token_out),
+    );
+    text!("Slippage: {}", slippage);
+    print_bound(
+        "Minimum received",
+        amount_out_min,
+        decimals_token_out,
+        &symbol_token_out,
+    );
+    text!(
         "Swap {} {} to {} {}...",
         to_human_readable(amount_in, decimals_token_in),
         symbol_token_in,
@@ -92,277 +125,762 @@ pub async fn swap_tokens(
         symbol_token_out
     );
 
-    //check allowance and approve if necessary
-    let allow = token_contract
-        .allowance(account, router_addr)
-        .call()
-        .await?;
-    if allow < amount_in {
-        //let's do an approve
-        println!(
-            "\n{}",
-            "Insufficient allowance, approve process incoming...".yellow()
-        );
-        // Start loader for approval
-        let loader = start_loader("Approving tokens...");
-        let receipt_approve = token_contract
-            .approve(router_addr, amount_in)
-            .send()
-            .await?
+    let route = match route {
+        SwapRoute::V2(route) => route,
+        SwapRoute::V3(route) => {
+            let router_v3_addr = network::v3()?.router;
+            let router_v3 = ISwapRouter::new(router_v3_addr, client_signed.clone());
+            approve_if_needed(
+                &token_contract,
+                account,
+                router_v3_addr,
+                amount_in,
+                decimals_token_in,
+                &symbol_token_in,
+            )
             .await?;
-        stop_loader(loader);
-        let receipt_approve_json: serde_json::Value = serde_json::to_value(&receipt_approve)?;
-        println!(
-            "Successfully approved {:?} {}\n",
-            to_human_readable(amount_in, decimals_token_in),
-            symbol_token_in
-        );
-        println!(
-            "Approve Receipt: {}",
-            receipt_approve_json["transactionHash"]
-                .as_str()
-                .unwrap_or("Unknown hash")
-        );
-    }
+            let receipt_swap = send_call(
+                &client_signed,
+                swap_call(&router_v3, &route, account, amount_out_min, deadline),
+                "Swapping tokens...",
+                &[
+                    Delta::spend(&symbol_token_in, decimals_token_in, amount_in),
+                    Delta::gain(&symbol_token_out, decimals_token_out, amount_out_min),
+                ],
+            )
+            .await?;
+            print_receipt("Swap", &receipt_swap)?;
+            return Ok(());
+        }
+    };
 
-    let loader = start_loader("Swapping tokens...");
-    let receipt_swap = router
-        .swap_exact_tokens_for_tokens(
-            amount_in,
-            amounts_out[1],
-            vec![token_in, token_out],
-            account,
-            U256::from(valid_timestamp),
+    //check allowance and approve if necessary
+    let approved = approve_if_needed(
+        &token_contract,
+        account,
+        router_addr,
+        amount_in,
+        decimals_token_in,
+        &symbol_token_in,
+    )
+    .await?;
+
+    //fee-on-transfer tokens deliver less than quoted and revert the plain swap
+    let received = if approved {
+        measure_output(amount_out, |min| {
+            router.swap_exact_tokens_for_tokens_supporting_fee_on_transfer_tokens(
+                amount_in,
+                min,
+                route.path.clone(),
+                account,
+                deadline,
+            )
+        })
+        .await?
+    } else {
+        amount_out
+    };
+
+    let receipt_swap = if received < amount_out {
+        print_tax("Route", tax_bps(amount_out, received));
+        let received_min = slippage.min_out(received);
+        text!(
+            "Fee-on-transfer detected, expecting at least {} {}",
+            to_human_readable(received_min, decimals_token_out),
+            symbol_token_out
+        );
+        send_call(
+            &client_signed,
+            router.swap_exact_tokens_for_tokens_supporting_fee_on_transfer_tokens(
+                amount_in,
+                received_min,
+                route.path,
+                account,
+                deadline,
+            ),
+            "Swapping tokens...",
+            &[
+                Delta::spend(&symbol_token_in, decimals_token_in, amount_in),
+                Delta::gain(&symbol_token_out, decimals_token_out, received_min),
+            ],
         )
-        .send()
         .await?
-        .await?;
-    stop_loader(loader);
-
-    let receipt_swap_json: serde_json::Value = serde_json::to_value(&receipt_swap)?;
-    println!(
-        "Swap Receipt: {}",
-        receipt_swap_json["transactionHash"]
-            .as_str()
-            .unwrap_or("Unknown hash")
+    } else {
+        send_call(
+            &client_signed,
+            router.swap_exact_tokens_for_tokens(
+                amount_in,
+                amount_out_min,
+                route.path,
+                account,
+                deadline,
+            ),
+            "Swapping tokens...",
+            &[
+                Delta::spend(&symbol_token_in, decimals_token_in, amount_in),
+                Delta::gain(&symbol_token_out, decimals_token_out, amount_out_min),
+            ],
+        )
+        .await?
+    };
+
+    print_receipt("Swap", &receipt_swap)?;
+
+    Ok(())
+}
+#[tokio::main]
+pub async fn swap_tokens_exact(
+    token_in_a: &String,
+    token_out_a: &String,
+    amount: &str,
+    slippage: &Slippage,
+    dex: &str,
+) -> Result<()> {
+    let client_signed = get_client_signed().await?;
+    let account = client_signed.address();
+    print_state(&client_signed).await?;
+
+    text!(
+        "{}",
+        "\n===== SWAP TOKENS FOR EXACT TOKENS =====\n".bold().blue()
     );
 
+    //Check for alias params
+    let token_in = alias::alias_token(token_in_a)?;
+    let token_out = alias::alias_token(token_out_a)?;
+    let (decimals_token_in, symbol_token_in, _) = get_token_metadata(token_in_a).await?;
+    let (decimals_token_out, symbol_token_out, _) = get_token_metadata(token_out_a).await?;
+
+    //params
+    let dex = network::dex(dex)?;
+    let router_addr = dex.router;
+    let router = IUniswapRouter::new(router_addr, client_signed.clone());
+    let token_contract = IERC20::new(token_in, client_signed.clone());
+
+    let deadline = deadline::get(&client_signed).await?;
+
+    let balance_of = at_block(token_contract.balance_of(account)).call().await?;
+    text!(
+        "{} {} ({}) {}",
+        "Actual Balance:".bold(),
+        to_human_readable(balance_of, decimals_token_in),
+        balance_of,
+        symbol_token_in
+    );
+
+    let amount_out = parse_exact(amount, decimals_token_out)?;
+
+    //AMOUNTIN
+    let route =
+        best_route_exact_out(client_signed.clone(), dex, token_in, token_out, amount_out).await?;
+    print_route(&route).await?;
+    check_price_impact(client_signed.clone(), dex, &route, true).await?;
+    let amount_in = route.amount_in();
+
+    let amount_in_max = slippage.max_in(amount_in);
+
+    check_balance(balance_of, amount_in_max)?;
+
+    output::field("slippageBps", slippage.bps());
+    output::field(
+        "amountInMax",
+        output::amount(amount_in_max, decimals_token_in),
+    );
+    output::field("amountOut", output::amount(amount_out, decimals_token_out));
+    text!("Slippage: {}", slippage);
+    print_bound(
+        "Maximum spent",
+        amount_in_max,
+        decimals_token_in,
+        &symbol_token_in,
+    );
+    text!(
+        "Swap at most {} {} for {} {}...",
+        to_human_readable(amount_in_max, decimals_token_in),
+        symbol_token_in,
+        to_human_readable(amount_out, decimals_token_out),
+        symbol_token_out
+    );
+
+    //check allowance and approve if necessary
+    let approved = approve_if_needed(
+        &token_contract,
+        account,
+        router_addr,
+        amount_in_max,
+        decimals_token_in,
+        &symbol_token_in,
+    )
+    .await?;
+
+    let swap = router.swap_tokens_for_exact_tokens(
+        amount_out,
+        amount_in_max,
+        route.path,
+        account,
+        deadline,
+    );
+    //there is no fee-on-transfer variant for exact output swaps
+    if approved {
+        if let Err(e) = at_block(swap.clone()).call().await {
+            return Err(exact_out_error(e.to_string(), "swaptoken").into());
+        }
+    }
+
+    let receipt_swap = send_call(
+        &client_signed,
+        swap,
+        "Swapping tokens...",
+        &[
+            Delta::spend(&symbol_token_in, decimals_token_in, amount_in),
+            Delta::gain(&symbol_token_out, decimals_token_out, amount_out),
+        ],
+    )
+    .await?;
+
+    print_receipt("Swap", &receipt_swap)?;
+
     Ok(())
 }
 #[tokio::main]
-pub async fn swap_eth(token_out_a: &String, amount: &String, slippage: &f64) -> Result<()> {
-    println!("{}", "\n===== SWAP ETH TO TOKEN =====\n".bold().blue());
+pub async fn swap_eth(
+    token_out_a: &String,
+    amount: &str,
+    slippage: &Slippage,
+    dex: &str,
+    protocol: &Protocol,
+) -> Result<()> {
+    text!("{}", "\n===== SWAP ETH TO TOKEN =====\n".bold().blue());
 
     let client = get_client().await?;
     let client_signed = get_client_signed().await?;
     let account = client_signed.address();
     print_state(&client_signed).await?;
 
-    println!("{}", "\n===== SWAP ETH =====\n".bold().blue());
+    text!("{}", "\n===== SWAP ETH =====\n".bold().blue());
 
     //params
-    let router_addr = "0x7a250d5630B4cF539739dF2C5dAcb4c659F2488D".parse::<Address>()?;
+    let dex = network::dex(dex)?;
+    let router_addr = dex.router;
     let router = IUniswapRouter::new(router_addr, client_signed.clone());
-    let token_in = "0xC02aaA39b223FE8D0A0e5C4F27eAD9083C756Cc2".parse::<Address>()?;
+    let token_in = network::get().weth;
     //Check for alias params
-    let token_out = alias::alias_token(token_out_a).unwrap();
-    let (decimals_token_out, symbol_token_out, _) = get_token_metadata(&token_out_a).await?;
+    let token_out = alias::alias_token(token_out_a)?;
+    let (decimals_token_out, symbol_token_out, _) = get_token_metadata(token_out_a).await?;
 
-    let valid_timestamp = get_valid_timestamp(300000);
-    let path = vec![token_in, token_out];
+    let deadline = deadline::get(&client_signed).await?;
 
-    let eth_balance = client.get_balance(account, None).await?;
+    let eth_balance = client.get_balance(account, settings::block_id()).await?;
 
-    println!(
+    text!(
         "{} {}",
         "Actual ETH Balance:".bold(),
         to_human_readable(eth_balance, 18)
     );
 
-    let amount_in = if amount == "max" {
-        eth_balance
+    //max and percentages leave the gas of the swap in the balance
+    let spendable = if is_relative(amount) {
+        //estimated on the route --protocol picks for the whole balance
+        let estimate = match pick_route(
+            client_signed.clone(),
+            *protocol,
+            dex,
+            token_in,
+            token_out,
+            eth_balance,
+        )
+        .await?
+        {
+            SwapRoute::V3(route) => {
+                let router_v3 = ISwapRouter::new(network::v3()?.router, client_signed.clone());
+                swap_call(&router_v3, &route, account, U256::zero(), deadline)
+                    .value(eth_balance)
+                    .tx
+            }
+            SwapRoute::V2(route) => {
+                router
+                    .swap_exact_eth_for_tokens_supporting_fee_on_transfer_tokens(
+                        U256::zero(),
+                        route.path,
+                        account,
+                        deadline,
+                    )
+                    .value(eth_balance)
+                    .tx
+            }
+        };
+        let reserve = gas::reserve(&client_signed, &estimate).await?;
+        print_reserve(&reserve);
+        eth_balance.saturating_sub(reserve.amount)
     } else {
-        U256::from_dec_str(amount).unwrap()
+        eth_balance
     };
+    let amount_in = parse_amount(amount, 18, spendable)?;
 
-    if eth_balance.is_zero() {
-        println!("{}", "ETH Balance is 0. Transaction cancelled.".red());
-        return Ok(());
-    }
-
-    if amount_in > eth_balance {
-        println!(
-            "{} {} ({}) {} ({})",
-            "Amount".red(),
-            amount_in,
-            "is greater than the balance".red(),
-            eth_balance,
-            "Transaction cancelled.".red()
-        );
-        return Ok(());
-    }
+    check_balance(spendable, amount_in)?;
 
     //AMOUNTOUT
-    let amounts_out = router.get_amounts_out(amount_in, path).call().await?;
-
-    let slippage_decimal = slippage / 100.0;
-    let amount_out_min = amounts_out[1]
-        - (amounts_out[1] * U256::from((slippage_decimal * 1e18) as u64) / U256::from(1e18 as u64));
-
-    println!("Slippage: {} %", slippage);
-    println!(
+    let route = select_route(
+        client_signed.clone(),
+        *protocol,
+        dex,
+        token_in,
+        token_out,
+        amount_in,
+        decimals_token_out,
+    )
+    .await?;
+    print_swap_route(&route).await?;
+    check_swap_impact(client_signed.clone(), dex, &route).await?;
+    let amount_out = route.amount_out();
+
+    let amount_out_min = slippage.min_out(amount_out);
+
+    output::field("slippageBps", slippage.bps());
+    output::field("amountIn", output::amount(amount_in, 18));
+    output::field(
+        "amountOutMin",
+        output::amount(amount_out_min, decimals_token_out),
+    );
+    text!("Slippage: {}", slippage);
+    print_bound(
+        "Minimum received",
+        amount_out_min,
+        decimals_token_out,
+        &symbol_token_out,
+    );
+    text!(
         "Swap {} ETH for {} {}...",
         to_human_readable(amount_in, 18),
         to_human_readable(amount_out_min, decimals_token_out),
         symbol_token_out
     );
-    let loader = start_loader("Swapping tokens...");
-    let receipt_swap = router
-        .swap_exact_eth_for_tokens(
-            amount_out_min,
-            vec![token_in, token_out],
-            account,
-            U256::from(valid_timestamp),
+
+    //the V3 router wraps the ETH sent with the call when the input token is WETH
+    let route = match route {
+        SwapRoute::V2(route) => route,
+        SwapRoute::V3(route) => {
+            let router_v3 = ISwapRouter::new(network::v3()?.router, client_signed.clone());
+            let receipt_swap = send_call(
+                &client_signed,
+                swap_call(&router_v3, &route, account, amount_out_min, deadline).value(amount_in),
+                "Swapping tokens...",
+                &[
+                    Delta::spend("ETH", 18, amount_in),
+                    Delta::gain(&symbol_token_out, decimals_token_out, amount_out_min),
+                ],
+            )
+            .await?;
+            print_receipt("Swap", &receipt_swap)?;
+            return Ok(());
+        }
+    };
+    //fee-on-transfer tokens deliver less than quoted, measure the buy tax before sending
+    let received = measure_output(amount_out, |min| {
+        router
+            .swap_exact_eth_for_tokens_supporting_fee_on_transfer_tokens(
+                min,
+                route.path.clone(),
+                account,
+                deadline,
+            )
+            .value(amount_in)
+    })
+    .await?;
+
+    let receipt_swap = if received < amount_out {
+        print_tax("Buy", tax_bps(amount_out, received));
+        let received_min = slippage.min_out(received);
+        text!(
+            "Fee-on-transfer detected, expecting at least {} {}",
+            to_human_readable(received_min, decimals_token_out),
+            symbol_token_out
+        );
+        send_call(
+            &client_signed,
+            router
+                .swap_exact_eth_for_tokens_supporting_fee_on_transfer_tokens(
+                    received_min,
+                    route.path,
+                    account,
+                    deadline,
+                )
+                .value(amount_in),
+            "Swapping tokens...",
+            &[
+                Delta::spend("ETH", 18, amount_in),
+                Delta::gain(&symbol_token_out, decimals_token_out, received_min),
+            ],
         )
-        .value(amount_in)
-        .send()
         .await?
-        .await?;
-    stop_loader(loader);
-    let receipt_swap_json: serde_json::Value = serde_json::to_value(&receipt_swap)?;
-    println!(
-        "Swap Receipt: {}",
-        receipt_swap_json["transactionHash"]
-            .as_str()
-            .unwrap_or("Unknown hash")
+    } else {
+        send_call(
+            &client_signed,
+            router
+                .swap_exact_eth_for_tokens(amount_out_min, route.path, account, deadline)
+                .value(amount_in),
+            "Swapping tokens...",
+            &[
+                Delta::spend("ETH", 18, amount_in),
+                Delta::gain(&symbol_token_out, decimals_token_out, amount_out_min),
+            ],
+        )
+        .await?
+    };
+    print_receipt("Swap", &receipt_swap)?;
+
+    Ok(())
+}
+#[tokio::main]
+pub async fn swap_to_eth(
+    token_in_a: &String,
+    amount: &str,
+    slippage: &Slippage,
+    dex: &str,
+) -> Result<()> {
+    let client_signed = get_client_signed().await?;
+    let account = client_signed.address();
+    print_state(&client_signed).await?;
+
+    text!("{}", "\n===== SWAP TOKEN TO ETH =====\n".bold().blue());
+
+    //Check for alias params
+    let token_in = alias::alias_token(token_in_a)?;
+    let (decimals_token_in, symbol_token_in, _) = get_token_metadata(token_in_a).await?;
+
+    //params
+    let dex = network::dex(dex)?;
+    let router_addr = dex.router;
+    let router = IUniswapRouter::new(router_addr, client_signed.clone());
+    let token_out = network::get().weth;
+    let token_contract = IERC20::new(token_in, client_signed.clone());
+
+    let deadline = deadline::get(&client_signed).await?;
+
+    let balance_of = at_block(token_contract.balance_of(account)).call().await?;
+    text!(
+        "{} {} ({}) {}",
+        "Actual Balance:".bold(),
+        to_human_readable(balance_of, decimals_token_in),
+        balance_of,
+        symbol_token_in
+    );
+
+    let amount_in = parse_amount(amount, decimals_token_in, balance_of)?;
+
+    check_balance(balance_of, amount_in)?;
+
+    //AMOUNTOUT
+    let route = best_route(client_signed.clone(), dex, token_in, token_out, amount_in).await?;
+    print_route(&route).await?;
+    check_price_impact(client_signed.clone(), dex, &route, false).await?;
+    let amount_out = route.amount_out();
+
+    let amount_out_min = slippage.min_out(amount_out);
+
+    output::field("slippageBps", slippage.bps());
+    output::field("amountIn", output::amount(amount_in, decimals_token_in));
+    output::field("amountOutMin", output::amount(amount_out_min, 18));
+    text!("Slippage: {}", slippage);
+    print_bound("Minimum received", amount_out_min, 18, "ETH");
+    text!(
+        "Swap {} {} for {} ETH...",
+        to_human_readable(amount_in, decimals_token_in),
+        symbol_token_in,
+        to_human_readable(amount_out_min, 18)
+    );
+
+    //check allowance and approve if necessary
+    let approved = approve_if_needed(
+        &token_contract,
+        account,
+        router_addr,
+        amount_in,
+        decimals_token_in,
+        &symbol_token_in,
+    )
+    .await?;
+
+    //fee-on-transfer tokens deliver less than quoted, measure the sell tax before sending
+    let received = if approved {
+        measure_output(amount_out, |min| {
+            router.swap_exact_tokens_for_eth_supporting_fee_on_transfer_tokens(
+                amount_in,
+                min,
+                route.path.clone(),
+                account,
+                deadline,
+            )
+        })
+        .await?
+    } else {
+        amount_out
+    };
+
+    let receipt_swap = if received < amount_out {
+        print_tax("Sell", tax_bps(amount_out, received));
+        let received_min = slippage.min_out(received);
+        text!(
+            "Fee-on-transfer detected, expecting at least {} ETH",
+            to_human_readable(received_min, 18)
+        );
+        send_call(
+            &client_signed,
+            router.swap_exact_tokens_for_eth_supporting_fee_on_transfer_tokens(
+                amount_in,
+                received_min,
+                route.path,
+                account,
+                deadline,
+            ),
+            "Swapping tokens...",
+            &[
+                Delta::spend(&symbol_token_in, decimals_token_in, amount_in),
+                Delta::gain("ETH", 18, received_min),
+            ],
+        )
+        .await?
+    } else {
+        send_call(
+            &client_signed,
+            router.swap_exact_tokens_for_eth(
+                amount_in,
+                amount_out_min,
+                route.path,
+                account,
+                deadline,
+            ),
+            "Swapping tokens...",
+            &[
+                Delta::spend(&symbol_token_in, decimals_token_in, amount_in),
+                Delta::gain("ETH", 18, amount_out_min),
+            ],
+        )
+        .await?
+    };
+
+    print_receipt("Swap", &receipt_swap)?;
+
+    Ok(())
+}
+// Failed simulation of an exact output swap. The pair sees a broken K invariant only when the
+// input token takes a fee on transfer, any other revert keeps its own reason
+fn exact_out_error(error: String, command: &str) -> Error {
+    let reason = revert_reason(&error);
+    if reason.contains("UniswapV2: K") {
+        return Error::Aborted(format!(
+            "swap simulation failed ({}), fee-on-transfer tokens can only be swapped with {}",
+            reason, command
+        ));
+    }
+    if deadline::is_expired(&reason) {
+        return deadline::expired_error(None);
+    }
+    Error::from_rpc(error)
+}
+
+#[tokio::main]
+pub async fn swap_eth_exact(
+    token_out_a: &String,
+    amount: &str,
+    slippage: &Slippage,
+    dex: &str,
+) -> Result<()> {
+    let client = get_client().await?;
+    let client_signed = get_client_signed().await?;
+    let account = client_signed.address();
+    print_state(&client_signed).await?;
+
+    text!(
+        "{}",
+        "\n===== SWAP ETH FOR EXACT TOKENS =====\n".bold().blue()
+    );
+
+    //params
+    let dex = network::dex(dex)?;
+    let router_addr = dex.router;
+    let router = IUniswapRouter::new(router_addr, client_signed.clone());
+    let token_in = network::get().weth;
+    //Check for alias params
+    let token_out = alias::alias_token(token_out_a)?;
+    let (decimals_token_out, symbol_token_out, _) = get_token_metadata(token_out_a).await?;
+
+    let deadline = deadline::get(&client_signed).await?;
+
+    let eth_balance = client.get_balance(account, settings::block_id()).await?;
+    text!(
+        "{} {}",
+        "Actual ETH Balance:".bold(),
+        to_human_readable(eth_balance, 18)
     );
 
+    let amount_out = parse_exact(amount, decimals_token_out)?;
+
+    //AMOUNTIN
+    let route =
+        best_route_exact_out(client_signed.clone(), dex, token_in, token_out, amount_out).await?;
+    print_route(&route).await?;
+    check_price_impact(client_signed.clone(), dex, &route, true).await?;
+    let amount_in = route.amount_in();
+
+    let amount_in_max = slippage.max_in(amount_in);
+
+    check_balance(eth_balance, amount_in_max)?;
+
+    output::field("slippageBps", slippage.bps());
+    output::field("amountInMax", output::amount(amount_in_max, 18));
+    output::field("amountOut", output::amount(amount_out, decimals_token_out));
+    text!("Slippage: {}", slippage);
+    print_bound("Maximum spent", amount_in_max, 18, "ETH");
+    text!(
+        "Swap at most {} ETH for {} {}...",
+        to_human_readable(amount_in_max, 18),
+        to_human_readable(amount_out, decimals_token_out),
+        symbol_token_out
+    );
+    //the router refunds the ETH not used by the swap
+    let swap = router
+        .swap_eth_for_exact_tokens(amount_out, route.path, account, deadline)
+        .value(amount_in_max);
+    //there is no fee-on-transfer variant for exact output swaps
+    if let Err(e) = at_block(swap.clone()).call().await {
+        return Err(exact_out_error(e.to_string(), "swapeth").into());
+    }
+
+    let receipt_swap = send_call(
+        &client_signed,
+        swap,
+        "Swapping tokens...",
+        &[
+            Delta::spend("ETH", 18, amount_in),
+            Delta::gain(&symbol_token_out, decimals_token_out, amount_out),
+        ],
+    )
+    .await?;
+    print_receipt("Swap", &receipt_swap)?;
+
     Ok(())
 }
 #[tokio::main]
-pub async fn approve(token_in_a: &String, spender_a: &String, amount: &String) -> Result<()> {
+pub async fn approve(token_in_a: &String, spender_a: &String, amount: &str) -> Result<()> {
     let client_signed = get_client_signed().await?;
     print_state(&client_signed).await?;
 
-    println!("{}", "\n===== APPROVE TOKEN =====\n".bold().blue());
+    text!("{}", "\n===== APPROVE TOKEN =====\n".bold().blue());
 
     // Check for alias params
-    let token_in = alias::alias_token(token_in_a).unwrap();
-    let (decimals_token_in, symbol_token_in, _) = get_token_metadata(&token_in_a).await?;
-    let spender = alias::alias_address(spender_a).unwrap();
-    let token_contract = IERC20::new(token_in, client_signed);
-    let amount_in = if amount == "max" {
-        println!("Approve {} {} to {} ", "MAX", symbol_token_in, spender_a);
+    let token_in = alias::alias_token(token_in_a)?;
+    let (decimals_token_in, symbol_token_in, _) = get_token_metadata(token_in_a).await?;
+    let spender = alias::alias_address(spender_a)?;
+    let token_contract = IERC20::new(token_in, client_signed.clone());
+    //max approves unlimited, a percentage is taken from the balance
+    let amount_in = if amount.trim().eq_ignore_ascii_case("max") {
+        text!("Approve MAX {} to {} ", symbol_token_in, spender_a);
         U256::MAX
     } else {
-        println!(
+        let balance_of = at_block(token_contract.balance_of(client_signed.address()))
+            .call()
+            .await?;
+        let amount_in = parse_amount(amount, decimals_token_in, balance_of)?;
+        text!(
             "Approve {} {} to {} ",
-            to_human_readable(U256::from_dec_str(amount).unwrap(), decimals_token_in),
+            to_human_readable(amount_in, decimals_token_in),
             symbol_token_in,
             spender_a
         );
-        U256::from_dec_str(amount).unwrap()
+        amount_in
     };
 
-    let loader = start_loader("Approving token...");
-    let receipt_approve = token_contract
-        .approve(spender, amount_in)
-        .send()
-        .await?
-        .await?;
-    stop_loader(loader);
-
-    let receipt_approve_json: serde_json::Value = serde_json::to_value(&receipt_approve)?;
-    println!(
-        "Approve Receipt: {}",
-        receipt_approve_json["transactionHash"]
-            .as_str()
-            .unwrap_or("Unknown hash")
-    );
+    output::field("spender", spender);
+    output::field("amount", output::amount(amount_in, decimals_token_in));
+    let receipt_approve = send_call(
+        &client_signed,
+        token_contract.approve(spender, amount_in),
+        "Approving token...",
+        &[],
+    )
+    .await?;
+
+    print_receipt("Approve", &receipt_approve)?;
 
     Ok(())
 }
 #[tokio::main]
-pub async fn transfer_eth(amount: &String, recipient_a: &String) -> Result<()> {
+pub async fn transfer_eth(amount: &str, recipient_a: &String) -> Result<()> {
     let client_signed = get_client_signed().await?;
     let account = client_signed.address();
     print_state(&client_signed).await?;
-    println!("{}", "\n===== TRANSFER ETH =====\n".bold().blue());
+    text!("{}", "\n===== TRANSFER ETH =====\n".bold().blue());
 
     //Check for alias params
-    let recipient = alias::alias_address(recipient_a).unwrap();
+    let recipient = alias::alias_address(recipient_a)?;
 
-    let balance = client_signed.get_balance(account, None).await?;
-    println!(
+    let balance = client_signed
+        .get_balance(account, settings::block_id())
+        .await?;
+    text!(
         "{} {} ETH ({} wei) \n",
         "Actual Balance:".bold(),
         to_human_readable(balance, 18),
         balance
     );
 
-    //param
-    let amount_in = if amount == "max" {
-        balance
+    //max and percentages leave the gas of the transfer in the balance
+    let spendable = if is_relative(amount) {
+        let estimate = Eip1559TransactionRequest::new()
+            .from(account)
+            .to(recipient)
+            .value(balance);
+        let reserve = gas::reserve(&client_signed, &estimate.into()).await?;
+        print_reserve(&reserve);
+        balance.saturating_sub(reserve.amount)
     } else {
-        U256::from_dec_str(amount).unwrap()
+        balance
     };
 
-    if balance.is_zero() {
-        println!("{}", "Balance is 0. Transaction cancelled.".red());
-        return Ok(());
-    }
+    //param
+    let amount_in = parse_amount(amount, 18, spendable)?;
 
-    if amount_in > balance {
-        println!(
-            "{} {} {} {} ({})",
-            "Amount".red(),
-            amount_in,
-            "is greater than the balance".red(),
-            balance,
-            "Transaction cancelled.".red()
-        );
-        return Ok(());
-    }
+    check_balance(spendable, amount_in)?;
 
-    println!(
+    output::field("recipient", recipient);
+    output::field("amount", output::amount(amount_in, 18));
+    text!(
         "Transfer {} ETH to address {}",
         to_human_readable(amount_in, 18),
         recipient
     );
     ////////////////:
-    let loader = start_loader("Transferring ETH...");
     let tx = Eip1559TransactionRequest::new()
         .to(recipient)
-        .value(amount_in)
-        .max_priority_fee_per_gas(U256::from(2000000000_u128)); // 2 Gwei
-    let receipt_transfer = client_signed.send_transaction(tx, None).await?.await?;
-    stop_loader(loader);
-    let receipt_transfer_json: serde_json::Value = serde_json::to_value(&receipt_transfer)?;
-    println!(
-        "Transfer Receipt: {}",
-        receipt_transfer_json["transactionHash"]
-            .as_str()
-            .unwrap_or("Unknown hash")
-    );
+        .value(amount_in);
+    let receipt_transfer = send_tx(
+        &client_signed,
+        tx.into(),
+        "Transferring ETH...",
+        &[Delta::spend("ETH", 18, amount_in)],
+    )
+    .await?;
+    print_receipt("Transfer", &receipt_transfer)?;
 
     Ok(())
 }
 
 #[tokio::main]
-pub async fn transfer_token(token_a: &String, amount: &String, recipient_a: &String) -> Result<()> {
+pub async fn transfer_token(token_a: &String, amount: &str, recipient_a: &String) -> Result<()> {
     let client_signed = get_client_signed().await?;
     let account = client_signed.address();
     print_state(&client_signed).await?;
-    println!("{}", "\n===== TRANSFER TOKEN =====\n".bold().blue());
+    text!("{}", "\n===== TRANSFER TOKEN =====\n".bold().blue());
 
     //Check for alias params
-    let token = alias::alias_token(token_a).unwrap();
-    let recipient = alias::alias_address(recipient_a).unwrap();
-    let (decimals_token, symbol_token, _) = get_token_metadata(&token_a).await?;
-    let token_contract = IERC20::new(token, client_signed);
+    let token = alias::alias_token(token_a)?;
+    let recipient = alias::alias_address(recipient_a)?;
+    let (decimals_token, symbol_token, _) = get_token_metadata(token_a).await?;
+    let token_contract = IERC20::new(token, client_signed.clone());
 
-    let balance_of = token_contract.balance_of(account).call().await?;
-    println!(
+    let balance_of = at_block(token_contract.balance_of(account)).call().await?;
+    text!(
         "{} {} ({}) {}\n",
         "Actual Balance:".bold(),
         to_human_readable(balance_of, decimals_token),
@@ -370,140 +888,107 @@ pub async fn transfer_token(token_a: &String, amount: &String, recipient_a: &Str
         symbol_token
     );
     //param
-    let amount_in = if amount == "max" {
-        balance_of
-    } else {
-        U256::from_dec_str(amount).unwrap()
-    };
-
-    if balance_of.is_zero() {
-        println!("{}", "Balance is 0. Transaction cancelled.".red());
-        return Ok(());
-    }
+    let amount_in = parse_amount(amount, decimals_token, balance_of)?;
 
-    if amount_in > balance_of {
-        println!(
-            "{} {} {} {} ({})",
-            "Amount".red(),
-            amount_in,
-            "is greater than the balance".red(),
-            balance_of,
-            "Transaction cancelled.".red()
-        );
-        return Ok(());
-    }
+    check_balance(balance_of, amount_in)?;
 
     //check allowance and approve if necessary
-    let allow = token_contract.allowance(account, recipient).call().await?;
+    let allow = at_block(token_contract.allowance(account, recipient))
+        .call()
+        .await?;
     if allow < amount_in {
         //let's do an approve
-        let loader = start_loader("Insufficient allowance, approve process incoming...");
-        let receipt_approve = token_contract
-            .approve(recipient, amount_in)
-            .send()
-            .await?
-            .await?;
-        stop_loader(loader);
-        let receipt_approve_json: serde_json::Value = serde_json::to_value(&receipt_approve)?;
-        println!(
-            "Successfully approved {:?} {}\n",
-            to_human_readable(balance_of, decimals_token),
-            symbol_token
-        );
-        println!(
-            "Approve Receipt: {}",
-            receipt_approve_json["transactionHash"]
-                .as_str()
-                .unwrap_or("Unknown hash")
-        );
-    }
-    println!(
-        "Transfer {:?} {} to address {:?}",
-        amount_in, symbol_token, recipient
-    );
-    let loader = start_loader("Transferring token...");
-    let receipt_transfer = token_contract
-        .transfer(recipient, amount_in)
-        .send()
-        .await?
+        let receipt_approve = send_call(
+            &client_signed,
+            token_contract.approve(recipient, amount_in),
+            "Insufficient allowance, approve process incoming...",
+            &[],
+        )
         .await?;
-    stop_loader(loader);
-
-    let receipt_transfer_json: serde_json::Value = serde_json::to_value(&receipt_transfer)?;
-    println!(
-        "Transfer Receipt: {}",
-        receipt_transfer_json["transactionHash"]
-            .as_str()
-            .unwrap_or("Unknown hash")
+        if receipt_approve.is_some() {
+            text!(
+                "Successfully approved {} {}\n",
+                to_human_readable(amount_in, decimals_token),
+                symbol_token
+            );
+        }
+        print_receipt("Approve", &receipt_approve)?;
+    }
+    output::field("token", token);
+    output::field("recipient", recipient);
+    output::field("amount", output::amount(amount_in, decimals_token));
+    text!(
+        "Transfer {} {} to address {:?}",
+        to_human_readable(amount_in, decimals_token),
+        symbol_token,
+        recipient
     );
+    let receipt_transfer = send_call(
+        &client_signed,
+        token_contract.transfer(recipient, amount_in),
+        "Transferring token...",
+        &[Delta::spend(&symbol_token, decimals_token, amount_in)],
+    )
+    .await?;
+
+    print_receipt("Transfer", &receipt_transfer)?;
 
     Ok(())
 }
 
-#[tokio::main]
-pub async fn wrap(amount: &String) -> Result<()> {
-    let provider_signed = get_client_signed().await?;
-    let account = provider_signed.address();
-    print_state(&provider_signed).await?;
-
-    //params
-    let weth_addr = "0xC02aaA39b223FE8D0A0e5C4F27eAD9083C756Cc2".parse::<Address>()?;
-    let weth_contract = IWETH::new(weth_addr, provider_signed);
-
-    //param
-    let amount_in;
-    if amount == "max" {
-        amount_in = weth_contract.balance_of(account).call().await?;
-    } else {
-        amount_in = U256::from_dec_str(amount).unwrap();
+// Insufficient balance error when the balance can't cover the amount
+pub fn check_balance(balance: U256, amount: U256) -> Result<(), Error> {
+    if balance.is_zero() {
+        return Err(Error::InsufficientBalance("balance is 0".to_string()));
     }
 
-    let receipt_wrap = weth_contract
-        .deposit()
-        .value(amount_in)
-        .send()
-        .await?
-        .await?;
-    println!("Wrap {:?} ethers into weth", amount_in);
-
-    println!(
-        "Transaction successful with hash: {:?}",
-        receipt_wrap.unwrap().transaction_hash
-    );
+    if amount > balance {
+        return Err(Error::InsufficientBalance(format!(
+            "amount {} is greater than the balance {}",
+            amount, balance
+        )));
+    }
 
     Ok(())
 }
 
-#[tokio::main]
-pub async fn unwrap(amount: &String) -> Result<()> {
-    let provider_signed = get_client_signed().await?;
-    let account = provider_signed.address();
-    print_state(&provider_signed).await?;
-
-    //params
-    let weth_addr = "0xC02aaA39b223FE8D0A0e5C4F27eAD9083C756Cc2".parse::<Address>()?;
-    let weth_contract = IWETH::new(weth_addr, provider_signed);
-
-    //param
-    let amount_in;
-    if amount == "max" {
-        amount_in = weth_contract.balance_of(account).call().await?;
-    } else {
-        amount_in = U256::from_dec_str(amount).unwrap();
-    }
-
-    let receipt_unwrap = weth_contract
-        .deposit()
-        .value(amount_in)
-        .send()
-        .await?
+// Approve spender when the allowance is too low, false when the allowance is still missing
+pub async fn approve_if_needed<M: Middleware + 'static>(
+    token_contract: &IERC20<M>,
+    owner: Address,
+    spender: Address,
+    amount: U256,
+    decimals: u8,
+    symbol: &str,
+) -> Result<bool> {
+    let allow = at_block(token_contract.allowance(owner, spender))
+        .call()
         .await?;
-    println!("Unwrap {:?} weth into ether", amount_in);
+    if allow >= amount {
+        return Ok(true);
+    }
 
-    println!(
-        "Transaction successful with hash: {:?}",
-        receipt_unwrap.unwrap().transaction_hash
+    //let's do an approve
+    text!(
+        "\n{}",
+        "Insufficient allowance, approve process incoming...".yellow()
     );
+    let receipt_approve = send_call(
+        &token_contract.client(),
+        token_contract.approve(spender, amount),
+        "Approving tokens...",
+        &[],
+    )
+    .await?;
+    if receipt_approve.is_some() {
+        text!(
+            "Successfully approved {} {}\n",
+            to_human_readable(amount, decimals),
+            symbol
+        );
+    }
+    print_receipt("Approve", &receipt_approve)?;
 
-    Ok(())
+    //with --dry-run the approve was only simulated, the allowance is still missing
+    Ok(receipt_approve.is_some())
 }
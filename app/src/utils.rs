@@ -1,59 +1,264 @@
-use ethers::{contract::abigen, core::types::U256};
+use ethers::{
+    abi::{Detokenize, RawLog},
+    contract::{abigen, builders::ContractCall, EthEvent},
+    core::types::{Address, Log, U256},
+    providers::Middleware,
+};
 use eyre::Result;
-use std::time::{SystemTime, UNIX_EPOCH};
 abigen!(IERC20, "./abi/erc20_abi.json");
+use crate::alias;
 use crate::client::get_client;
-#[path = "alias.rs"]
-mod alias;
-trait ToF64 {
-    fn as_f64(&self) -> f64;
+use crate::error::Error;
+use crate::multicall::{decode, Multicall};
+use crate::settings;
+
+// Exact decimal string of a raw amount, with thousands separators and every significant decimal
+pub fn to_human_readable(value: U256, decimals: u8) -> String {
+    format_units(value, decimals, None)
 }
 
-impl ToF64 for U256 {
-    fn as_f64(&self) -> f64 {
-        let mut result = 0.0;
-        let mut base = 1.0;
+// Split value into integer and fractional digits, keeping at most `significant` significant
+// digits in the fractional part (truncated, the integer part is always complete)
+pub fn format_units(value: U256, decimals: u8, significant: Option<usize>) -> String {
+    //with more decimals than a U256 holds, the whole value is fractional
+    let (integer, fraction) = match pow10(decimals) {
+        Ok(factor) => (value / factor, value % factor),
+        Err(_) => (U256::zero(), value),
+    };
+    let integer = integer.to_string();
+    let mut fraction = format!(
+        "{:0>width$}",
+        fraction.to_string(),
+        width = decimals as usize
+    );
+
+    if let Some(significant) = significant {
+        let kept = if integer != "0" {
+            significant.saturating_sub(integer.len())
+        } else {
+            //leading zeros of a small amount are not significant
+            let zeros = fraction.len() - fraction.trim_start_matches('0').len();
+            zeros + significant
+        };
+        fraction.truncate(kept);
+    }
+    let fraction = fraction.trim_end_matches('0');
 
-        for i in 0..4 {
-            let word = self.0[i] as f64;
-            result += word * base;
-            base *= 2.0_f64.powi(64);
+    //thousands separators on the integer part
+    let mut grouped = String::new();
+    for (i, digit) in integer.chars().enumerate() {
+        if i > 0 && (integer.len() - i).is_multiple_of(3) {
+            grouped.push(',');
         }
+        grouped.push(digit);
+    }
 
-        result
+    if fraction.is_empty() {
+        grouped
+    } else {
+        format!("{}.{}", grouped, fraction)
     }
 }
 
-pub fn to_human_readable(balance: U256, decimals: u8) -> f64 {
-    let factor = U256::exp10(decimals as usize); // 10^decimals
+// 10^decimals, a token can declare up to 255 decimals but a U256 holds 10^77 at most
+pub fn pow10(decimals: u8) -> Result<U256> {
+    U256::from(10)
+        .checked_pow(U256::from(decimals))
+        .ok_or_else(|| Error::Input(format!("{} decimals do not fit in a U256", decimals)).into())
+}
 
-    let balance_f64 = balance.as_f64();
-    let factor_f64 = factor.as_f64();
+// Inverse of format_units: exact raw amount of a decimal string, "1,234.5" with 18 decimals
+pub fn parse_units(value: &str, decimals: u8) -> Result<U256> {
+    let value = value.trim();
+    let (integer, fraction) = value.split_once('.').unwrap_or((value, ""));
+    //separators must split the integer part in groups of 3, "1,5" is rejected
+    let groups: Vec<&str> = integer.split(',').collect();
+    let grouped = groups.len() == 1
+        || (!groups[0].is_empty()
+            && groups[0].len() <= 3
+            && groups[1..].iter().all(|g| g.len() == 3));
+    if !grouped {
+        return Err(Error::Input(format!("Invalid amount '{}'", value)).into());
+    }
+    let integer = groups.concat();
+    if (integer.is_empty() && fraction.is_empty())
+        || !integer.chars().all(|c| c.is_ascii_digit())
+        || !fraction.chars().all(|c| c.is_ascii_digit())
+    {
+        return Err(Error::Input(format!("Invalid amount '{}'", value)).into());
+    }
+    if fraction.len() > decimals as usize {
+        return Err(Error::Input(format!(
+            "Amount '{}' has more than {} decimals",
+            value, decimals
+        ))
+        .into());
+    }
 
-    balance_f64 / factor_f64
+    let digits = format!(
+        "{}{:0<width$}",
+        integer,
+        fraction,
+        width = decimals as usize
+    );
+    let digits = digits.trim_start_matches('0');
+    if digits.is_empty() {
+        return Ok(U256::zero());
+    }
+    Ok(U256::from_dec_str(digits)
+        .map_err(|_| Error::Input(format!("Amount '{}' is too large", value)))?)
+}
+
+// Approximate float of a raw amount, for ratios and prices only, never for displayed amounts
+pub fn to_f64(value: U256, decimals: u8) -> f64 {
+    format_units(value, decimals, None)
+        .replace(',', "")
+        .parse()
+        .unwrap_or(f64::MAX)
 }
 
 pub async fn get_token_metadata(token_add: &String) -> Result<(u8, String, String)> {
     let client = get_client().await?;
 
     //Check for alias params
-    let token = alias::alias_token(token_add).unwrap();
+    let token = alias::alias_token(token_add)?;
+
+    let token_contract = IERC20::new(token, client.clone());
+
+    //decimals, symbol and name in one round-trip
+    let decimals = token_contract.decimals();
+    let symbol = token_contract.symbol();
+    let name = token_contract.name();
+    let results = Multicall::new(client)
+        .add(&decimals)
+        .add(&symbol)
+        .add(&name)
+        .call()
+        .await?;
+
+    Ok((
+        decode(&decimals, &results[0])?,
+        decode(&symbol, &results[1])?,
+        decode(&name, &results[2])?,
+    ))
+}
+
+pub struct TokenInfo {
+    pub decimals: u8,
+    pub symbol: String,
+    pub balance: U256,
+    pub allowance: U256,
+}
+
+// Metadata, balance of owner and allowance to spender in one round-trip
+pub async fn get_token_info(
+    token_add: &String,
+    owner: Address,
+    spender: Address,
+) -> Result<TokenInfo> {
+    let client = get_client().await?;
+
+    //Check for alias params
+    let token = alias::alias_token(token_add)?;
 
-    let token_contract = IERC20::new(token, client);
+    let token_contract = IERC20::new(token, client.clone());
 
-    let decimals = token_contract.decimals().call().await?;
+    let decimals = token_contract.decimals();
+    let symbol = token_contract.symbol();
+    let balance = token_contract.balance_of(owner);
+    let allowance = token_contract.allowance(owner, spender);
+    let results = Multicall::new(client)
+        .add(&decimals)
+        .add(&symbol)
+        .add(&balance)
+        .add(&allowance)
+        .call()
+        .await?;
 
-    let symbol: String = token_contract.symbol().call().await?;
+    Ok(TokenInfo {
+        decimals: decode(&decimals, &results[0])?,
+        symbol: decode(&symbol, &results[1])?,
+        balance: decode(&balance, &results[2])?,
+        allowance: decode(&allowance, &results[3])?,
+    })
+}
 
-    let name: String = token_contract.name().call().await?;
+// Decode every log of type E emitted by the given contract
+pub fn decode_logs<E: EthEvent>(logs: &[Log], emitter: Address) -> Vec<E> {
+    logs.iter()
+        .filter(|log| log.address == emitter)
+        .filter_map(|log| {
+            E::decode_log(&RawLog::from((log.topics.clone(), log.data.to_vec()))).ok()
+        })
+        .collect()
+}
 
-    Ok((decimals, symbol, name))
+// Read call at --block with --dry-run, at the latest block otherwise
+pub fn at_block<M: Middleware, D: Detokenize>(mut call: ContractCall<M, D>) -> ContractCall<M, D> {
+    call.block = settings::block_id();
+    call
 }
 
-pub fn get_valid_timestamp(future_millis: u128) -> u128 {
-    let start = SystemTime::now();
-    let since_epoch = start.duration_since(UNIX_EPOCH).unwrap();
-    let time_millis = since_epoch.as_millis().checked_add(future_millis).unwrap();
+#[cfg(test)]
+mod tests {
+    use super::*;
 
-    time_millis
+    #[test]
+    fn formats_with_separators() {
+        let value = U256::from_dec_str("1234500000000000000000").unwrap();
+        assert_eq!(format_units(value, 18, None), "1,234.5");
+        assert_eq!(format_units(U256::from(1_000_000), 6, None), "1");
+        assert_eq!(format_units(U256::from(1), 6, None), "0.000001");
+        assert_eq!(format_units(U256::from(123_456_789), 6, Some(4)), "123.4");
+        assert_eq!(format_units(U256::from(1_234), 6, Some(2)), "0.0012");
+        //beyond 77 decimals, no U256 reaches one unit
+        assert_eq!(
+            format_units(U256::from(5), 78, None),
+            format!("0.{}5", "0".repeat(77))
+        );
+        assert_eq!(
+            format_units(U256::MAX, 255, Some(2)).len(),
+            2 + 255 - 78 + 2
+        );
+        assert!(pow10(77).is_ok());
+        assert!(pow10(78).is_err());
+    }
+
+    #[test]
+    fn parses_what_format_units_prints() {
+        for (input, decimals) in [
+            ("1,234.5", 18),
+            ("1,000,000", 6),
+            ("0.000001", 6),
+            ("42", 0),
+        ] {
+            let value = parse_units(input, decimals).unwrap();
+            assert_eq!(format_units(value, decimals, None), input);
+        }
+        assert_eq!(
+            parse_units("1,234.5", 18).unwrap(),
+            U256::from_dec_str("1234500000000000000000").unwrap()
+        );
+        assert_eq!(parse_units(".5", 2).unwrap(), U256::from(50));
+        assert_eq!(parse_units("0", 18).unwrap(), U256::zero());
+    }
+
+    #[test]
+    fn rejects_invalid_amounts() {
+        for input in [
+            "1,5",
+            ",123",
+            "1,2345",
+            "12,34,567",
+            "",
+            ".",
+            "1.2.3",
+            "-1",
+            "1e18",
+        ] {
+            assert!(parse_units(input, 18).is_err(), "{} accepted", input);
+        }
+        assert!(parse_units("1.0000001", 6).is_err());
+        assert!(parse_units(&"9".repeat(80), 0).is_err());
+    }
 }
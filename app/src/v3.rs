@@ -0,0 +1,275 @@
+use colored::Colorize;
+use ethers::{
+    contract::{abigen, builders::ContractCall},
+    core::types::{Address, Bytes, U256, U512},
+    providers::Middleware,
+};
+use eyre::Result;
+use serde_json::json;
+use std::sync::Arc;
+abigen!(IQuoterV2, "./abi/quoter_univ3.json");
+abigen!(ISwapRouter, "./abi/router_univ3.json");
+abigen!(IUniswapV3Factory, "./abi/factory_univ3.json");
+abigen!(IUniswapV3Pool, "./abi/pool_univ3.json");
+
+use crate::{
+    error::Error,
+    network::V3,
+    output::{self, text},
+    router::base_tokens,
+    utils::{at_block, get_token_metadata, to_human_readable},
+};
+
+// Fee tiers of the V3 pools in hundredths of a bip: 0.01, 0.05, 0.3 and 1 %
+pub const FEE_TIERS: [u32; 4] = [100, 500, 3000, 10000];
+
+#[derive(Debug, Clone)]
+pub struct V3Route {
+    pub path: Vec<Address>,
+    // Fee tier of each hop
+    pub fees: Vec<u32>,
+    pub amount_in: U256,
+    pub amount_out: U256,
+}
+
+impl V3Route {
+    // Packed path of exactInput: token, fee (uint24), token, fee, ..., token
+    pub fn encoded_path(&self) -> Bytes {
+        let mut path = Vec::new();
+        for (i, token) in self.path.iter().enumerate() {
+            path.extend_from_slice(token.as_bytes());
+            if let Some(fee) = self.fees.get(i) {
+                path.extend_from_slice(&fee.to_be_bytes()[1..]);
+            }
+        }
+        path.into()
+    }
+}
+
+// Fee tier giving the largest output for one hop, None when no pool can fill amount_in
+async fn best_tier<M: Middleware + 'static>(
+    quoter: &IQuoterV2<M>,
+    token_in: Address,
+    token_out: Address,
+    amount_in: U256,
+) -> Option<(u32, U256)> {
+    let mut best: Option<(u32, U256)> = None;
+    for fee in FEE_TIERS {
+        let params = QuoteExactInputSingleParams {
+            token_in,
+            token_out,
+            amount_in,
+            fee,
+            sqrt_price_limit_x96: U256::zero(),
+        };
+        //the quoter reverts when the pool does not exist or lacks liquidity
+        if let Ok((amount_out, _, _, _)) = at_block(quoter.quote_exact_input_single(params))
+            .call()
+            .await
+        {
+            if best.is_none_or(|(_, b)| amount_out > b) {
+                best = Some((fee, amount_out));
+            }
+        }
+    }
+    best
+}
+
+// Quote the direct pools of every fee tier and the 2-hop paths through the base tokens with
+// QuoterV2, and keep the one with the largest output
+pub async fn best_route_v3<M: Middleware + 'static>(
+    client: Arc<M>,
+    v3: &V3,
+    token_in: Address,
+    token_out: Address,
+    amount_in: U256,
+) -> Result<V3Route> {
+    let quoter = IQuoterV2::new(v3.quoter, client);
+
+    let mut best: Option<V3Route> = None;
+    if let Some((fee, amount_out)) = best_tier(&quoter, token_in, token_out, amount_in).await {
+        best = Some(V3Route {
+            path: vec![token_in, token_out],
+            fees: vec![fee],
+            amount_in,
+            amount_out,
+        });
+    }
+    for base in base_tokens() {
+        if base == token_in || base == token_out {
+            continue;
+        }
+        let Some((fee_in, amount_mid)) = best_tier(&quoter, token_in, base, amount_in).await else {
+            continue;
+        };
+        let Some((fee_out, amount_out)) = best_tier(&quoter, base, token_out, amount_mid).await
+        else {
+            continue;
+        };
+        if best.as_ref().is_none_or(|b| amount_out > b.amount_out) {
+            best = Some(V3Route {
+                path: vec![token_in, base, token_out],
+                fees: vec![fee_in, fee_out],
+                amount_in,
+                amount_out,
+            });
+        }
+    }
+
+    best.ok_or_else(|| {
+        Error::Aborted(format!(
+            "No Uniswap V3 route found between {:?} and {:?}",
+            token_in, token_out
+        ))
+        .into()
+    })
+}
+
+// Spot price of each hop of the route: sqrtPriceX96 of the pool (slot0) and whether the input
+// token is token0 of the pool
+pub async fn spot_prices<M: Middleware + 'static>(
+    client: Arc<M>,
+    v3: &V3,
+    route: &V3Route,
+) -> Result<Vec<(U256, bool)>> {
+    let quoter = IQuoterV2::new(v3.quoter, client.clone());
+    let factory = IUniswapV3Factory::new(at_block(quoter.factory()).call().await?, client.clone());
+    let mut prices = Vec::new();
+    for (hop, fee) in route.path.windows(2).zip(&route.fees) {
+        let pool_addr = at_block(factory.get_pool(hop[0], hop[1], *fee))
+            .call()
+            .await?;
+        if pool_addr.is_zero() {
+            return Err(Error::Aborted(format!(
+                "No Uniswap V3 pool for {:?} and {:?} at {}",
+                hop[0],
+                hop[1],
+                fee_percent(*fee)
+            ))
+            .into());
+        }
+        let pool = IUniswapV3Pool::new(pool_addr, client.clone());
+        let (sqrt_price_x96, ..) = at_block(pool.slot_0()).call().await?;
+        prices.push((sqrt_price_x96, hop[0] < hop[1]));
+    }
+    Ok(prices)
+}
+
+// Output of amount_in at the spot prices of the hops, without fee nor price impact.
+// The price is sqrtPriceX96^2 / 2^192 in token1 per token0, in U512 to keep it exact
+pub fn mid_amount_out_v3(amount_in: U256, prices: &[(U256, bool)]) -> U256 {
+    prices
+        .iter()
+        .fold(amount_in, |amount, (sqrt_price_x96, zero_for_one)| {
+            if sqrt_price_x96.is_zero() {
+                return U256::zero();
+            }
+            let amount = U512::from(amount);
+            let price_x192 = U512::from(*sqrt_price_x96) * U512::from(*sqrt_price_x96);
+            let out = if *zero_for_one {
+                amount
+                    .checked_mul(price_x192)
+                    .map_or(U512::MAX, |product| product >> 192)
+            } else {
+                (amount << 192) / price_x192
+            };
+            U256::try_from(out).unwrap_or(U256::MAX)
+        })
+}
+
+// exactInputSingle for a direct pool, exactInput for a multi-hop path
+pub fn swap_call<M: Middleware + 'static>(
+    router: &ISwapRouter<M>,
+    route: &V3Route,
+    recipient: Address,
+    amount_out_min: U256,
+    deadline: U256,
+) -> ContractCall<M, U256> {
+    if route.path.len() == 2 {
+        router.exact_input_single(ExactInputSingleParams {
+            token_in: route.path[0],
+            token_out: route.path[1],
+            fee: route.fees[0],
+            recipient,
+            deadline,
+            amount_in: route.amount_in,
+            amount_out_minimum: amount_out_min,
+            sqrt_price_limit_x96: U256::zero(),
+        })
+    } else {
+        router.exact_input(ExactInputParams {
+            path: route.encoded_path(),
+            recipient,
+            deadline,
+            amount_in: route.amount_in,
+            amount_out_minimum: amount_out_min,
+        })
+    }
+}
+
+fn fee_percent(fee: u32) -> String {
+    format!("{}.{:02} %", fee / 10000, fee % 10000 / 100)
+}
+
+pub async fn print_route_v3(route: &V3Route) -> Result<()> {
+    let mut symbols = Vec::new();
+    let mut decimals = Vec::new();
+    for token in &route.path {
+        let (decimals_token, symbol_token, _) = get_token_metadata(&format!("{:?}", token)).await?;
+        symbols.push(symbol_token);
+        decimals.push(decimals_token);
+    }
+    let last = route.path.len() - 1;
+
+    output::field(
+        "route",
+        json!({
+            "path": route.path,
+            "symbols": symbols,
+            "fees": route.fees,
+            "amountIn": output::amount(route.amount_in, decimals[0]),
+            "amountOut": output::amount(route.amount_out, decimals[last]),
+        }),
+    );
+    let mut hops = symbols[0].clone();
+    for i in 0..last {
+        hops.push_str(&format!(
+            " -({})-> {}",
+            fee_percent(route.fees[i]),
+            symbols[i + 1]
+        ));
+    }
+    text!("{} {}", "Route (V3):".bold(), hops);
+    text!(
+        "  {} {} -> {} {}",
+        to_human_readable(route.amount_in, decimals[0]),
+        symbols[0],
+        to_human_readable(route.amount_out, decimals[last]),
+        symbols[last]
+    );
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn mid_amount_out_from_sqrt_prices() {
+        let one = U256::one() << 96;
+        let amount = U256::exp10(18);
+        //price 1, then price 4 token1 per token0 (sqrtPriceX96 = 2 << 96)
+        assert_eq!(mid_amount_out_v3(amount, &[(one, true)]), amount);
+        assert_eq!(mid_amount_out_v3(amount, &[(one * 2, true)]), amount * 4);
+        assert_eq!(mid_amount_out_v3(amount, &[(one * 2, false)]), amount / 4);
+        assert_eq!(
+            mid_amount_out_v3(amount, &[(one * 2, true), (one * 2, false)]),
+            amount
+        );
+        assert_eq!(
+            mid_amount_out_v3(amount, &[(U256::zero(), true)]),
+            U256::zero()
+        );
+    }
+}
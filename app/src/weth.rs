@@ -0,0 +1,151 @@
+use colored::Colorize;
+use ethers::{
+    contract::abigen,
+    core::types::{TransactionReceipt, U256},
+    providers::Middleware,
+};
+use eyre::Result;
+abigen!(IWETH, "./abi/weth_abi.json");
+
+use crate::{
+    amount::{is_relative, parse_amount},
+    client::{get_client_signed, print_state},
+    gas::{self, print_reserve},
+    network,
+    output::{self, text},
+    sender::{print_receipt, send_call, Delta},
+    settings,
+    tx::check_balance,
+    utils::{at_block, decode_logs, to_human_readable},
+};
+
+#[tokio::main]
+pub async fn wrap(amount: &str) -> Result<()> {
+    let provider_signed = get_client_signed().await?;
+    let account = provider_signed.address();
+    print_state(&provider_signed).await?;
+
+    text!("{}", "\n===== WRAP ETH =====\n".bold().blue());
+
+    //params
+    let weth_addr = network::get().weth;
+    let weth_contract = IWETH::new(weth_addr, provider_signed.clone());
+
+    let eth_balance = provider_signed
+        .get_balance(account, settings::block_id())
+        .await?;
+    text!(
+        "{} {} ETH",
+        "Actual ETH Balance:".bold(),
+        to_human_readable(eth_balance, 18)
+    );
+    //max and percentages leave the gas of the deposit in the balance
+    let spendable = if is_relative(amount) {
+        let reserve = gas::reserve(
+            &provider_signed,
+            &weth_contract.deposit().value(eth_balance).tx,
+        )
+        .await?;
+        print_reserve(&reserve);
+        eth_balance.saturating_sub(reserve.amount)
+    } else {
+        eth_balance
+    };
+    let amount_in = parse_amount(amount, 18, spendable)?;
+    check_balance(spendable, amount_in)?;
+
+    output::field("amount", output::amount(amount_in, 18));
+    text!("Wrap {} ETH into WETH", to_human_readable(amount_in, 18));
+    let receipt_wrap = send_call(
+        &provider_signed,
+        weth_contract.deposit().value(amount_in),
+        "Wrapping ETH...",
+        &[
+            Delta::spend("ETH", 18, amount_in),
+            Delta::gain("WETH", 18, amount_in),
+        ],
+    )
+    .await?;
+
+    print_receipt("Wrap", &receipt_wrap)?;
+    if let Some(receipt) = &receipt_wrap {
+        let deposited = decode_logs::<DepositFilter>(&receipt.logs, weth_addr)
+            .into_iter()
+            .filter(|d| d.dst == account)
+            .fold(U256::zero(), |acc, d| acc + d.wad);
+        confirm("Deposit", "Wrapped", deposited, amount_in, receipt);
+    }
+
+    Ok(())
+}
+
+#[tokio::main]
+pub async fn unwrap(amount: &str) -> Result<()> {
+    let provider_signed = get_client_signed().await?;
+    let account = provider_signed.address();
+    print_state(&provider_signed).await?;
+
+    text!("{}", "\n===== UNWRAP WETH =====\n".bold().blue());
+
+    //params
+    let weth_addr = network::get().weth;
+    let weth_contract = IWETH::new(weth_addr, provider_signed.clone());
+
+    //param
+    let balance_of = at_block(weth_contract.balance_of(account)).call().await?;
+    text!(
+        "{} {} WETH",
+        "Actual WETH Balance:".bold(),
+        to_human_readable(balance_of, 18)
+    );
+    let amount_in = parse_amount(amount, 18, balance_of)?;
+    check_balance(balance_of, amount_in)?;
+
+    output::field("amount", output::amount(amount_in, 18));
+    text!("Unwrap {} WETH into ETH", to_human_readable(amount_in, 18));
+    let receipt_unwrap = send_call(
+        &provider_signed,
+        weth_contract.withdraw(amount_in),
+        "Unwrapping WETH...",
+        &[
+            Delta::spend("WETH", 18, amount_in),
+            Delta::gain("ETH", 18, amount_in),
+        ],
+    )
+    .await?;
+
+    print_receipt("Unwrap", &receipt_unwrap)?;
+    if let Some(receipt) = &receipt_unwrap {
+        let withdrawn = decode_logs::<WithdrawalFilter>(&receipt.logs, weth_addr)
+            .into_iter()
+            .filter(|w| w.src == account)
+            .fold(U256::zero(), |acc, w| acc + w.wad);
+        confirm("Withdrawal", "Unwrapped", withdrawn, amount_in, receipt);
+    }
+
+    Ok(())
+}
+
+// Amount of the WETH event against the amount sent, they differ only with a non standard WETH.
+// The transaction is already mined, a mismatch is a warning and not a failure of the command
+fn confirm(event: &str, label: &str, amount: U256, expected: U256, receipt: &TransactionReceipt) {
+    output::field("confirmed", output::amount(amount, 18));
+    if amount != expected {
+        let warning = format!(
+            "{} event of {:?} is {} WETH, expected {} WETH",
+            event,
+            receipt.transaction_hash,
+            to_human_readable(amount, 18),
+            to_human_readable(expected, 18)
+        );
+        text!("{} {}", "Warning:".yellow().bold(), warning);
+        output::field("warning", warning);
+        return;
+    }
+    text!(
+        "{} {} WETH ({} event)",
+        label,
+        to_human_readable(amount, 18),
+        event
+    );
+}